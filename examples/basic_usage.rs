@@ -56,7 +56,9 @@ fn main() {
     println!("\n🔍 Accessing after expiration:");
     match cache.get(&"user:alice".to_string()) {
         Ok(user) => println!("✅ Found: {}", user.value()),
-        Err(SimpleCacheError::Expired) => println!("⏰ Alice's data expired and was auto-removed"),
+        Err(SimpleCacheError::Expired { .. }) => {
+            println!("⏰ Alice's data expired and was auto-removed")
+        }
         Err(e) => println!("❌ Error: {}", e),
     }
 
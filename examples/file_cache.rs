@@ -110,8 +110,9 @@ fn main() {
     match file_cache.get(&"/src/main.rs".to_string()) {
         Ok(file_obj) => {
             let file = file_obj.value();
-            println!("✅ Found main.rs: {} bytes", file.size);
+            println!("✅ Found {}: {} bytes", file.path, file.size);
             println!("   Content: {}", file.content);
+            println!("   Last modified: {:?}", file.last_modified);
             println!("   Age: {:?}", file_obj.age());
         }
         Err(e) => println!("❌ Error: {}", e),
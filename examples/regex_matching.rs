@@ -172,7 +172,7 @@ fn main() {
     // Test email pattern
     match RegexMatcher::email_pattern() {
         Ok(email_matcher) => {
-            println!("📧 Finding valid email addresses:");
+            println!("📧 {}:", email_matcher.description);
             let emails = data_cache.get_all_by_matcher(&email_matcher);
 
             for (key, _) in emails {
@@ -314,7 +314,7 @@ fn main() {
     println!("\n🔬 Advanced regex example - parsing structured log entries:");
 
     // Add some log entries
-    let log_entries = vec![
+    let log_entries = [
         "[2024-01-15 10:30:45] INFO: User alice logged in from 192.168.1.100",
         "[2024-01-15 10:31:02] ERROR: Database connection failed for user bob",
         "[2024-01-15 10:31:15] WARN: High memory usage detected: 85%",
@@ -0,0 +1,396 @@
+//! Pluggable second-level storage behind a `SimpleCacher`.
+
+use crate::{EntrySource, Matcher, SimpleCacheObject, SimpleCacher};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// A backing store that a `SimpleCacher` can sit in front of.
+///
+/// Implement this trait for a slower, larger store (a local database file, a
+/// remote key-value service, ...) and wrap it in a `LayeredCacher` to get an
+/// in-memory L1 with read-through and write-through to the store.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::collections::BTreeMap;
+///
+/// // A toy store that keeps everything in a sorted map
+/// struct SortedStore(BTreeMap<u32, String>);
+///
+/// impl CacheBackend<u32, String> for SortedStore {
+///     type Error = std::convert::Infallible;
+///
+///     fn get(&mut self, key: &u32) -> Result<Option<String>, Self::Error> {
+///         Ok(self.0.get(key).cloned())
+///     }
+///
+///     fn put(&mut self, key: &u32, value: &String) -> Result<(), Self::Error> {
+///         self.0.insert(*key, value.clone());
+///         Ok(())
+///     }
+///
+///     fn remove(&mut self, key: &u32) -> Result<(), Self::Error> {
+///         self.0.remove(key);
+///         Ok(())
+///     }
+///
+///     fn scan(&mut self, matcher: &dyn Matcher<u32>) -> Result<Vec<(u32, String)>, Self::Error> {
+///         Ok(self
+///             .0
+///             .iter()
+///             .filter(|(key, _)| matcher.matches(key))
+///             .map(|(key, value)| (*key, value.clone()))
+///             .collect())
+///     }
+/// }
+/// ```
+pub trait CacheBackend<T, U> {
+    /// Error returned when the store can't be accessed.
+    type Error;
+
+    /// Fetches the value stored under `key`, or `None` if there is none.
+    fn get(&mut self, key: &T) -> Result<Option<U>, Self::Error>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn put(&mut self, key: &T, value: &U) -> Result<(), Self::Error>;
+
+    /// Deletes the value stored under `key`. Deleting a missing key is not an error.
+    fn remove(&mut self, key: &T) -> Result<(), Self::Error>;
+
+    /// Returns every stored entry whose key matches `matcher`.
+    fn scan(&mut self, matcher: &dyn Matcher<T>) -> Result<Vec<(T, U)>, Self::Error>;
+}
+
+impl<T, U> CacheBackend<T, U> for HashMap<T, U>
+where
+    T: Clone + Eq + Hash,
+    U: Clone,
+{
+    type Error = Infallible;
+
+    fn get(&mut self, key: &T) -> Result<Option<U>, Self::Error> {
+        Ok(HashMap::get(self, key).cloned())
+    }
+
+    fn put(&mut self, key: &T, value: &U) -> Result<(), Self::Error> {
+        self.insert(key.clone(), value.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &T) -> Result<(), Self::Error> {
+        HashMap::remove(self, key);
+        Ok(())
+    }
+
+    fn scan(&mut self, matcher: &dyn Matcher<T>) -> Result<Vec<(T, U)>, Self::Error> {
+        Ok(self
+            .iter()
+            .filter(|(key, _)| matcher.matches(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// A `SimpleCacher` acting as an L1 cache in front of a `CacheBackend`.
+///
+/// Reads are served from memory when possible and otherwise read through to
+/// the backend, caching the result. Writes and removals go to the backend
+/// first and only touch the in-memory cache once the backend has accepted
+/// them, so the L1 never holds data the backend doesn't have.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::collections::HashMap;
+/// use std::time::Duration;
+///
+/// let l1 = SimpleCacher::with_max_size(Duration::from_secs(60), 100);
+/// let mut cache = LayeredCacher::new(l1, HashMap::new());
+///
+/// // Write-through: stored in both layers
+/// cache.insert("user:1".to_string(), "Alice".to_string()).unwrap();
+/// assert_eq!(cache.backend().len(), 1);
+///
+/// // Read-through: misses in memory are loaded from the backend
+/// cache.cache_mut().clear();
+/// let entry = cache.get(&"user:1".to_string()).unwrap().unwrap();
+/// assert_eq!(entry.value(), "Alice");
+/// assert_eq!(cache.cache().len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LayeredCacher<T, U, B> {
+    cache: SimpleCacher<T, U>,
+    backend: B,
+}
+
+impl<T, U, B> LayeredCacher<T, U, B>
+where
+    T: Clone + Eq + Hash,
+    B: CacheBackend<T, U>,
+{
+    /// Puts `cache` in front of `backend`.
+    ///
+    /// The TTL and size limit of `cache` apply to the in-memory layer only.
+    /// The backend is the only store written to: a writer configured on
+    /// `cache` isn't used by the layered cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The in-memory L1 cache
+    /// * `backend` - The backing store
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cache` has a size limit of zero, since it couldn't hold the
+    /// entries read through.
+    pub fn new(cache: SimpleCacher<T, U>, backend: B) -> Self {
+        assert!(
+            cache.check_capacity().is_ok(),
+            "the in-memory layer must hold at least one entry"
+        );
+        Self { cache, backend }
+    }
+
+    /// Retrieves an entry, reading through to the backend on a miss.
+    ///
+    /// Entries that are missing or expired in memory are fetched from the
+    /// backend and cached with the default TTL. Keys recorded as missing with
+    /// `SimpleCacher::insert_negative` are reported as absent without asking
+    /// the backend.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(&SimpleCacheObject<U>))` - The entry, from memory or freshly loaded
+    /// * `Ok(None)` - Neither layer has the key
+    /// * `Err(B::Error)` - The backend couldn't be read
+    pub fn get(&mut self, key: &T) -> Result<Option<&SimpleCacheObject<U>>, B::Error> {
+        if self.cache.is_negative(key) {
+            return Ok(None);
+        }
+        if self.cache.contains_key(key) {
+            return Ok(self.cache.get(key).ok());
+        }
+
+        let Some(value) = self.backend.get(key)? else {
+            return Ok(None);
+        };
+        let mut cache_obj =
+            SimpleCacheObject::with_source(value, self.cache.max_age, EntrySource::Loaded);
+        cache_obj.record_access();
+
+        self.cache.insert_object(key.clone(), cache_obj);
+        Ok(self.cache.cache.get(key))
+    }
+
+    /// Writes an entry to the backend, then caches it with the default TTL.
+    ///
+    /// # Errors
+    ///
+    /// Returns the backend error if the write fails; the in-memory cache is
+    /// left unchanged in that case.
+    pub fn insert(&mut self, key: T, value: U) -> Result<(), B::Error> {
+        let cache_obj = SimpleCacheObject::new(value, self.cache.max_age);
+        self.store(key, cache_obj)
+    }
+
+    /// Writes an entry to the backend, then caches it with a custom TTL.
+    ///
+    /// # Errors
+    ///
+    /// Returns the backend error if the write fails; the in-memory cache is
+    /// left unchanged in that case.
+    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) -> Result<(), B::Error> {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.store(key, cache_obj)
+    }
+
+    /// Writes an entry to the backend and caches it in memory, bypassing the
+    /// in-memory layer's writer.
+    fn store(&mut self, key: T, cache_obj: SimpleCacheObject<U>) -> Result<(), B::Error> {
+        self.backend.put(&key, cache_obj.value())?;
+        self.cache.insert_object(key, cache_obj);
+        Ok(())
+    }
+
+    /// Removes an entry from the backend and then from memory.
+    ///
+    /// # Returns
+    ///
+    /// The in-memory entry, if there was one
+    ///
+    /// # Errors
+    ///
+    /// Returns the backend error if the removal fails; the in-memory cache
+    /// is left unchanged in that case.
+    pub fn remove(&mut self, key: &T) -> Result<Option<SimpleCacheObject<U>>, B::Error> {
+        self.backend.remove(key)?;
+        Ok(self.cache.remove(key))
+    }
+
+    /// Returns every entry in the backend whose key matches `matcher`.
+    ///
+    /// The backend is the source of truth, so this queries it directly
+    /// without populating the in-memory cache.
+    pub fn scan<M>(&mut self, matcher: &M) -> Result<Vec<(T, U)>, B::Error>
+    where
+        M: Matcher<T>,
+    {
+        self.backend.scan(matcher)
+    }
+
+    /// Returns the in-memory layer.
+    pub fn cache(&self) -> &SimpleCacher<T, U> {
+        &self.cache
+    }
+
+    /// Returns the in-memory layer for direct manipulation.
+    ///
+    /// Changes made here are not written to the backend.
+    pub fn cache_mut(&mut self) -> &mut SimpleCacher<T, U> {
+        &mut self.cache
+    }
+
+    /// Returns the backing store.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backing store for direct manipulation.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Splits the layered cache into its in-memory cache and backend.
+    pub fn into_parts(self) -> (SimpleCacher<T, U>, B) {
+        (self.cache, self.backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RangeMatcher;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Debug, Default)]
+    struct FlakyBackend {
+        entries: HashMap<u32, String>,
+        reads: usize,
+        fail: bool,
+    }
+
+    impl CacheBackend<u32, String> for FlakyBackend {
+        type Error = &'static str;
+
+        fn get(&mut self, key: &u32) -> Result<Option<String>, Self::Error> {
+            self.reads += 1;
+            if self.fail {
+                return Err("backend down");
+            }
+            Ok(self.entries.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &u32, value: &String) -> Result<(), Self::Error> {
+            if self.fail {
+                return Err("backend down");
+            }
+            self.entries.insert(*key, value.clone());
+            Ok(())
+        }
+
+        fn remove(&mut self, key: &u32) -> Result<(), Self::Error> {
+            if self.fail {
+                return Err("backend down");
+            }
+            self.entries.remove(key);
+            Ok(())
+        }
+
+        fn scan(&mut self, matcher: &dyn Matcher<u32>) -> Result<Vec<(u32, String)>, Self::Error> {
+            Ok(self
+                .entries
+                .iter()
+                .filter(|(key, _)| matcher.matches(key))
+                .map(|(key, value)| (*key, value.clone()))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_layered_read_and_write_through() {
+        let l1 = SimpleCacher::with_max_size(Duration::from_millis(30), 2);
+        let mut cache = LayeredCacher::new(l1, FlakyBackend::default());
+
+        for i in 1..=3 {
+            cache.insert(i, format!("value{}", i)).unwrap();
+        }
+        assert_eq!(cache.backend().entries.len(), 3);
+        assert_eq!(cache.cache().len(), 2);
+
+        // Hits don't touch the backend, evicted keys are read through
+        assert_eq!(cache.get(&3).unwrap().unwrap().value(), "value3");
+        assert_eq!(cache.backend().reads, 0);
+        assert_eq!(cache.get(&1).unwrap().unwrap().value(), "value1");
+        assert_eq!(cache.backend().reads, 1);
+        assert!(cache.cache().contains_key(&1));
+        assert!(cache.get(&9).unwrap().is_none());
+
+        // Expired L1 entries are reloaded
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(cache.get(&1).unwrap().unwrap().value(), "value1");
+        assert_eq!(cache.backend().reads, 3);
+
+        // Failed writes leave memory untouched
+        cache.backend_mut().fail = true;
+        assert_eq!(cache.insert(1, "changed".to_string()), Err("backend down"));
+        assert!(cache.remove(&1).is_err());
+        assert_eq!(cache.cache_mut().get(&1).unwrap().value(), "value1");
+        assert!(matches!(cache.get(&2), Err("backend down")));
+
+        cache.backend_mut().fail = false;
+        assert!(cache.remove(&1).unwrap().is_some());
+        assert!(cache.get(&1).unwrap().is_none());
+
+        let mut found = cache.scan(&RangeMatcher::new(2, 3)).unwrap();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![(2, "value2".to_string()), (3, "value3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_layered_l1_limits() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A zero-size L1 couldn't hold anything read through
+        let l1: SimpleCacher<u32, String> = SimpleCacher::with_max_size(Duration::from_secs(60), 0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            LayeredCacher::new(l1, FlakyBackend::default())
+        }));
+        assert!(result.is_err());
+
+        // Writes go to the backend only, not to a writer on the L1
+        let writes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&writes);
+        let mut l1 = SimpleCacher::new(Duration::from_secs(60));
+        l1.set_writer(move |_: &u32, _: &String| -> std::io::Result<()> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        let mut cache = LayeredCacher::new(l1, FlakyBackend::default());
+        cache.insert(1, "one".to_string()).unwrap();
+        cache
+            .insert_with_ttl(2, "two".to_string(), Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(writes.load(Ordering::SeqCst), 0);
+        assert_eq!(cache.backend().entries.len(), 2);
+        assert_eq!(cache.cache().len(), 2);
+    }
+}
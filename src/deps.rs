@@ -0,0 +1,84 @@
+//! Dependency edges between cache entries used for cascading invalidation.
+
+use indexmap::{Equivalent, IndexMap};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Parent/child relationships between cache keys.
+///
+/// Edges are keyed by the parent key regardless of whether the parent is
+/// currently cached, so a parent that expires and is later re-inserted still
+/// invalidates the entries derived from its old value.
+#[derive(Debug, Clone)]
+pub(crate) struct DependencyGraph<T> {
+    dependents: IndexMap<T, HashSet<T>>,
+    parents: HashMap<T, Vec<T>>,
+}
+
+impl<T> Default for DependencyGraph<T> {
+    fn default() -> Self {
+        Self {
+            dependents: IndexMap::new(),
+            parents: HashMap::new(),
+        }
+    }
+}
+
+impl<T> DependencyGraph<T>
+where
+    T: Eq + Hash,
+{
+    /// Records that `child` depends on `parent`.
+    pub(crate) fn add(&mut self, child: &T, parent: &T)
+    where
+        T: Clone,
+    {
+        let inserted = self
+            .dependents
+            .entry(parent.clone())
+            .or_default()
+            .insert(child.clone());
+        if inserted {
+            self.parents
+                .entry(child.clone())
+                .or_default()
+                .push(parent.clone());
+        }
+    }
+
+    /// Drops the edges from `child` to the keys it depends on.
+    ///
+    /// Entries that depend on `child` are left untouched.
+    pub(crate) fn remove_child(&mut self, child: &T) {
+        let Some(parents) = self.parents.remove(child) else {
+            return;
+        };
+        for parent in parents {
+            if let Some(children) = self.dependents.get_mut(&parent) {
+                children.remove(child);
+                if children.is_empty() {
+                    self.dependents.swap_remove(&parent);
+                }
+            }
+        }
+    }
+
+    /// Returns the keys `child` depends on.
+    pub(crate) fn parents(&self, child: &T) -> &[T] {
+        self.parents.get(child).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Removes and returns the keys that directly depend on `parent`.
+    pub(crate) fn take_dependents<Q>(&mut self, parent: &Q) -> HashSet<T>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.dependents.swap_remove(parent).unwrap_or_default()
+    }
+
+    /// Removes all edges.
+    pub(crate) fn clear(&mut self) {
+        self.dependents.clear();
+        self.parents.clear();
+    }
+}
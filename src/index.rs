@@ -0,0 +1,134 @@
+//! Ordered secondary key index.
+//!
+//! `SimpleCacher` stores its entries in a `Store`, a hash-indexed linked slab
+//! that only supports exact lookups. When a cache has its key index enabled,
+//! every key is also kept in a sorted set so that prefix and range queries can
+//! jump straight to the matching keys instead of scanning every entry.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Bound;
+
+/// Type-erased ordered set of cache keys.
+///
+/// The cache itself only requires `T: Eq + Hash`, so the `Ord` bound needed by
+/// the sorted set is hidden behind this trait and only required when the
+/// index is enabled. Indexes must be `Send + Sync` so the cache stays
+/// shareable between threads.
+pub(crate) trait KeyIndex<T>: fmt::Debug + Send + Sync {
+    /// Adds a key to the index. Adding an existing key is a no-op.
+    fn insert(&mut self, key: &T);
+
+    /// Removes a key from the index if present.
+    fn remove(&mut self, key: &T);
+
+    /// Removes all keys from the index.
+    fn clear(&mut self);
+
+    /// Returns the indexed keys within the given bounds, in ascending order.
+    fn range<'a>(
+        &'a self,
+        lower: Bound<&T>,
+        upper: Bound<&T>,
+    ) -> Box<dyn Iterator<Item = &'a T> + 'a>;
+
+    /// Clones the index into a new box.
+    fn clone_box(&self) -> Box<dyn KeyIndex<T>>;
+}
+
+impl<T> Clone for Box<dyn KeyIndex<T>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// `KeyIndex` implementation backed by a `BTreeSet`.
+#[derive(Clone)]
+pub(crate) struct BTreeKeyIndex<T> {
+    keys: BTreeSet<T>,
+}
+
+impl<T> fmt::Debug for BTreeKeyIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BTreeKeyIndex")
+            .field("len", &self.keys.len())
+            .finish()
+    }
+}
+
+impl<T> BTreeKeyIndex<T>
+where
+    T: Ord + Clone,
+{
+    /// Builds an index containing the given keys.
+    pub(crate) fn from_keys<'a, I>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self {
+            keys: keys.into_iter().cloned().collect(),
+        }
+    }
+}
+
+impl<T> KeyIndex<T> for BTreeKeyIndex<T>
+where
+    T: Ord + Clone + Send + Sync + 'static,
+{
+    fn insert(&mut self, key: &T) {
+        if !self.keys.contains(key) {
+            self.keys.insert(key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &T) {
+        self.keys.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    fn range<'a>(
+        &'a self,
+        lower: Bound<&T>,
+        upper: Bound<&T>,
+    ) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        // `BTreeSet::range` panics on inverted or empty-exclusive bounds, so
+        // treat those as an empty range instead.
+        let empty = match (lower, upper) {
+            (Bound::Included(lo), Bound::Included(hi)) => lo > hi,
+            (Bound::Included(lo), Bound::Excluded(hi))
+            | (Bound::Excluded(lo), Bound::Included(hi))
+            | (Bound::Excluded(lo), Bound::Excluded(hi)) => lo >= hi,
+            _ => false,
+        };
+
+        if empty {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(self.keys.range::<T, _>((lower, upper)))
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn KeyIndex<T>> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns the smallest string that is greater than every string starting
+/// with `prefix`, or `None` if no such string exists.
+pub(crate) fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+
+    while let Some(last) = chars.pop() {
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+
+    None
+}
@@ -0,0 +1,590 @@
+//! Append-only journal for crash-safe persistence.
+//!
+//! A `JournaledCacher` pairs a snapshot file (see `SimpleCacher::save_to`)
+//! with a journal file next to it. Every mutation is appended to the journal
+//! before it is acknowledged, and on startup the snapshot is loaded and the
+//! journal replayed on top of it. Compaction writes a fresh snapshot and
+//! truncates the journal.
+//!
+//! Each journal record is framed as a little-endian `u32` payload length, a
+//! `u32` CRC-32 of the payload, and the bincode-encoded payload. A torn write
+//! at the end of the journal is detected on replay and cut off.
+//!
+//! Records are numbered, and each snapshot stores the number of the last
+//! record it includes. Replay skips records the snapshot already covers, so a
+//! compaction that crashes after writing the snapshot but before truncating
+//! the journal doesn't apply them twice.
+
+use crate::persist::bincode_error;
+use crate::{SimpleCacheError, SimpleCacheObject, SimpleCacher};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Serialize)]
+enum RecordRef<'a, T, U> {
+    Insert {
+        key: &'a T,
+        entry: &'a SimpleCacheObject<U>,
+    },
+    Remove {
+        key: &'a T,
+    },
+    Clear,
+}
+
+#[derive(Deserialize)]
+enum Record<T, U> {
+    Insert { key: T, entry: SimpleCacheObject<U> },
+    Remove { key: T },
+    Clear,
+}
+
+/// A `SimpleCacher` whose mutations are logged to an append-only journal.
+///
+/// Every `insert`, `insert_with_ttl`, `remove` and `clear` is written to the
+/// journal before it returns, so the cache can be rebuilt after a crash by
+/// loading the last snapshot and replaying the journal. Once the journal
+/// holds more records than the compaction threshold, it is folded into a new
+/// snapshot automatically; `compact` does the same on demand.
+///
+/// Reads go through `get` or the underlying cache returned by `cache`.
+/// Tags, dependencies and in-place edits through `get_mut` are not journaled.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::time::Duration;
+///
+/// let path = std::env::temp_dir().join("simple_cacher_journal_doc.snap");
+/// # let _ = std::fs::remove_file(&path);
+/// # let _ = std::fs::remove_file(path.with_extension("snap.wal"));
+///
+/// {
+///     let mut cache = JournaledCacher::open(&path, Duration::from_secs(300)).unwrap();
+///     cache.insert("user:1".to_string(), "Alice".to_string()).unwrap();
+///     cache.insert("user:2".to_string(), "Bob".to_string()).unwrap();
+///     cache.remove(&"user:2".to_string()).unwrap();
+/// } // Dropped without compacting, as if the process had crashed
+///
+/// let mut cache: JournaledCacher<String, String> =
+///     JournaledCacher::open(&path, Duration::from_secs(300)).unwrap();
+/// assert_eq!(cache.get(&"user:1".to_string()).unwrap().value(), "Alice");
+/// assert_eq!(cache.cache().len(), 1);
+/// # std::fs::remove_file(&path).ok();
+/// # std::fs::remove_file(path.with_extension("snap.wal")).ok();
+/// ```
+#[derive(Debug)]
+pub struct JournaledCacher<T, U> {
+    cache: SimpleCacher<T, U>,
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    journal: File,
+    records: usize,
+    seq: u64,
+    compact_after: Option<usize>,
+    sync_on_write: bool,
+}
+
+/// Default number of journal records after which the journal is compacted.
+const DEFAULT_COMPACT_AFTER: usize = 10_000;
+
+impl<T, U> JournaledCacher<T, U>
+where
+    T: Clone + Eq + Hash + Serialize + DeserializeOwned,
+    U: Serialize + DeserializeOwned,
+{
+    /// Opens a journaled cache, restoring any state left at `path`.
+    ///
+    /// `path` is the snapshot file; the journal is kept next to it with a
+    /// `.wal` suffix. If a snapshot exists it is loaded, then the journal is
+    /// replayed on top of it. Expired entries are dropped and remaining TTLs
+    /// are rebased onto the current clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the snapshot file
+    /// * `max_age` - Default time-to-live for cache entries
+    pub fn open<P>(path: P, max_age: Duration) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(path.as_ref(), max_age, None)
+    }
+
+    /// Opens a size-limited journaled cache, restoring any state left at `path`.
+    ///
+    /// See `open` and `SimpleCacher::with_max_size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the snapshot file
+    /// * `max_age` - Default time-to-live for cache entries
+    /// * `max_size` - Maximum number of entries to keep in the cache
+    pub fn open_with_max_size<P>(path: P, max_age: Duration, max_size: usize) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(path.as_ref(), max_age, Some(max_size))
+    }
+
+    fn open_with(path: &Path, max_age: Duration, max_size: Option<usize>) -> io::Result<Self> {
+        let snapshot_path = path.to_path_buf();
+        let mut journal_name = path.as_os_str().to_owned();
+        journal_name.push(".wal");
+        let journal_path = PathBuf::from(journal_name);
+
+        let (mut cache, covered) = match File::open(&snapshot_path) {
+            Ok(file) => SimpleCacher::read_snapshot(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (SimpleCacher::new(max_age), 0),
+            Err(err) => return Err(err),
+        };
+        cache.max_age = Some(max_age);
+        cache.max_size = max_size;
+
+        let (records, seq) = replay(&journal_path, &mut cache, covered)?;
+        cache.cleanup_expired();
+        if let Some(max_size) = max_size {
+            cache.evict_to(max_size);
+        }
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+
+        Ok(Self {
+            cache,
+            snapshot_path,
+            journal_path,
+            journal,
+            records,
+            seq,
+            compact_after: Some(DEFAULT_COMPACT_AFTER),
+            sync_on_write: false,
+        })
+    }
+
+    /// Inserts an entry with the default TTL and logs it to the journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal can't be written. The entry is still
+    /// inserted into the in-memory cache in that case.
+    ///
+    /// Also returns an error wrapping the `SimpleCacheError` if the cache
+    /// rejects the entry, for example because its size limit is zero. Nothing
+    /// is logged then unless the entry was cached anyway.
+    pub fn insert(&mut self, key: T, value: U) -> io::Result<()> {
+        let stored = self.cache.try_insert(key.clone(), value);
+        self.log_insert(&key, stored)
+    }
+
+    /// Inserts an entry with a custom TTL and logs it to the journal.
+    ///
+    /// # Errors
+    ///
+    /// See `insert`.
+    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) -> io::Result<()> {
+        let stored = self.cache.try_insert_with_ttl(key.clone(), value, ttl);
+        self.log_insert(&key, stored)
+    }
+
+    /// Removes an entry and logs the removal to the journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal can't be written. The entry is still
+    /// removed from the in-memory cache in that case.
+    pub fn remove(&mut self, key: &T) -> io::Result<Option<SimpleCacheObject<U>>> {
+        let removed = self.cache.remove(key);
+        self.seq += 1;
+        let payload = encode::<T, U>(self.seq, &RecordRef::Remove { key })?;
+        self.append(&payload)?;
+        Ok(removed)
+    }
+
+    /// Removes all entries and logs the operation to the journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal can't be written.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.cache.clear();
+        self.seq += 1;
+        let payload = encode::<T, U>(self.seq, &RecordRef::Clear)?;
+        self.append(&payload)
+    }
+
+    /// Retrieves an entry by key. See `SimpleCacher::get`.
+    pub fn get(&mut self, key: &T) -> Result<&SimpleCacheObject<U>, SimpleCacheError> {
+        self.cache.get(key)
+    }
+
+    /// Returns the underlying cache for read-only queries.
+    pub fn cache(&self) -> &SimpleCacher<T, U> {
+        &self.cache
+    }
+
+    /// Writes a new snapshot and truncates the journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot can't be written or the journal can't
+    /// be truncated. A failed compaction leaves the cache recoverable: either
+    /// the previous snapshot is kept, or the new one records which journal
+    /// entries it already includes.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.cache.save_snapshot(&self.snapshot_path, self.seq)?;
+        self.journal.set_len(0)?;
+        self.journal.sync_all()?;
+        self.records = 0;
+        Ok(())
+    }
+
+    /// Sets how many journal records trigger an automatic compaction.
+    ///
+    /// Pass `None` to only compact when `compact` is called. Defaults to
+    /// 10,000 records.
+    pub fn set_compaction_threshold(&mut self, records: Option<usize>) {
+        self.compact_after = records;
+    }
+
+    /// Controls whether every journal write is synced to disk.
+    ///
+    /// By default records are handed to the operating system on every write,
+    /// which survives a crash of the process but not of the machine. Enabling
+    /// this also calls `fsync` after each record, at a significant cost.
+    pub fn set_sync_on_write(&mut self, sync: bool) {
+        self.sync_on_write = sync;
+    }
+
+    /// Returns the path of the journal file.
+    pub fn journal_path(&self) -> &Path {
+        &self.journal_path
+    }
+
+    /// Returns the number of records written since the last compaction.
+    pub fn journal_len(&self) -> usize {
+        self.records
+    }
+
+    /// Logs the entry for a key that was just inserted, if the cache holds it,
+    /// and reports the outcome of the insert.
+    fn log_insert(
+        &mut self,
+        key: &T,
+        stored: Result<Option<U>, SimpleCacheError>,
+    ) -> io::Result<()> {
+        if let Some(entry) = self.cache.cache.get(key) {
+            self.seq += 1;
+            let payload = encode(self.seq, &RecordRef::Insert { key, entry })?;
+            self.append(&payload)?;
+        }
+        stored
+            .map(drop)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "journal record too large"))?;
+
+        let mut frame = Vec::with_capacity(8 + payload.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&crc32(payload).to_le_bytes());
+        frame.extend_from_slice(payload);
+        self.journal.write_all(&frame)?;
+        if self.sync_on_write {
+            self.journal.sync_data()?;
+        }
+
+        self.records += 1;
+        if self
+            .compact_after
+            .is_some_and(|limit| self.records >= limit)
+        {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a journal record together with its sequence number.
+fn encode<T, U>(seq: u64, record: &RecordRef<T, U>) -> io::Result<Vec<u8>>
+where
+    T: Serialize,
+    U: Serialize,
+{
+    bincode::serialize(&(seq, record)).map_err(|err| bincode_error(*err))
+}
+
+/// Applies every intact record after `covered` in the journal to `cache`.
+///
+/// Stops at the first truncated or corrupted record and cuts the journal off
+/// there, so later appends don't follow garbage. A journal holding only
+/// records up to `covered`, which the snapshot already includes, is emptied.
+/// Returns the number of records applied and the sequence number of the
+/// last one, or `covered` if there were none.
+fn replay<T, U>(
+    path: &Path,
+    cache: &mut SimpleCacher<T, U>,
+    covered: u64,
+) -> io::Result<(usize, u64)>
+where
+    T: Clone + Eq + Hash + DeserializeOwned,
+    U: DeserializeOwned,
+{
+    let mut bytes = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => file.read_to_end(&mut bytes)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, covered)),
+        Err(err) => return Err(err),
+    };
+
+    let mut offset = 0;
+    let mut records = 0;
+    let mut last_seq = covered;
+    while let Some(payload) = next_frame(&bytes[offset..]) {
+        let Ok((seq, record)) = bincode::deserialize::<(u64, Record<T, U>)>(payload) else {
+            break;
+        };
+        offset += 8 + payload.len();
+        if seq <= covered {
+            continue;
+        }
+        match record {
+            Record::Insert { key, entry } => {
+                cache.insert_object(key, entry);
+            }
+            Record::Remove { key } => {
+                cache.remove(&key);
+            }
+            Record::Clear => cache.clear(),
+        }
+        records += 1;
+        last_seq = seq;
+    }
+
+    if records == 0 {
+        offset = 0;
+    }
+    if offset < bytes.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(offset as u64)?;
+    }
+
+    Ok((records, last_seq))
+}
+
+/// Returns the payload of the frame at the start of `bytes`, if it is complete
+/// and its checksum matches.
+///
+/// A torn frame, whose header or payload was cut short or whose checksum
+/// doesn't match, yields `None` rather than an error so that replay can cut
+/// the journal off in front of it.
+fn next_frame(bytes: &[u8]) -> Option<&[u8]> {
+    let len = usize::try_from(u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?)).ok()?;
+    let checksum = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+    let payload = bytes.get(8..len.checked_add(8)?)?;
+    (crc32(payload) == checksum).then_some(payload)
+}
+
+/// Computes the CRC-32 (IEEE) checksum of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_journal_replay_and_compaction() {
+        let path =
+            std::env::temp_dir().join(format!("simple_cacher_journal_{}.snap", std::process::id()));
+
+        let journal_path = {
+            let mut cache = JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+            cache.insert(1u32, "one".to_string()).unwrap();
+            cache.insert(2, "two".to_string()).unwrap();
+            cache.clear().unwrap();
+            cache.insert(3, "three".to_string()).unwrap();
+            cache
+                .insert_with_ttl(4, "four".to_string(), Duration::from_millis(20))
+                .unwrap();
+            cache.insert(5, "five".to_string()).unwrap();
+            assert!(cache.remove(&5).unwrap().is_some());
+            assert_eq!(cache.journal_len(), 7);
+            cache.journal_path().to_path_buf()
+        };
+        thread::sleep(Duration::from_millis(50));
+
+        // Simulate a torn write at the end of the journal
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal_path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, &[42, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let mut cache: JournaledCacher<u32, String> =
+            JournaledCacher::open_with_max_size(&path, Duration::from_secs(60), 3).unwrap();
+        assert_eq!(cache.journal_len(), 7);
+        assert_eq!(cache.cache().len(), 1);
+        assert_eq!(cache.get(&3).unwrap().value(), "three");
+
+        cache.set_compaction_threshold(Some(9));
+        cache.insert(6, "six".to_string()).unwrap();
+        assert_eq!(cache.journal_len(), 8);
+        cache.insert(7, "seven".to_string()).unwrap();
+        assert_eq!(cache.journal_len(), 0);
+        assert_eq!(std::fs::metadata(&journal_path).unwrap().len(), 0);
+        drop(cache);
+
+        let mut cache: JournaledCacher<u32, String> =
+            JournaledCacher::open_with_max_size(&path, Duration::from_secs(60), 3).unwrap();
+        assert_eq!(cache.journal_len(), 0);
+        let keys: Vec<u32> = cache.cache().iter_active().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [3, 6, 7]);
+        assert_eq!(cache.get(&7).unwrap().value(), "seven");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&journal_path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_interrupted_compaction() {
+        let path = std::env::temp_dir().join(format!(
+            "simple_cacher_journal_interrupted_{}.snap",
+            std::process::id()
+        ));
+
+        let journal_path = {
+            let mut cache = JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+            cache.insert(1u32, "one".to_string()).unwrap();
+            cache.insert(2, "two".to_string()).unwrap();
+            cache.insert(3, "three".to_string()).unwrap();
+            cache.remove(&2).unwrap();
+
+            // Crash after the snapshot was written but before the journal
+            // was truncated
+            let journal = std::fs::read(cache.journal_path()).unwrap();
+            cache.compact().unwrap();
+            std::fs::write(cache.journal_path(), journal).unwrap();
+            cache.journal_path().to_path_buf()
+        };
+
+        let mut cache: JournaledCacher<u32, String> =
+            JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+        assert_eq!(cache.journal_len(), 0);
+        assert_eq!(std::fs::metadata(&journal_path).unwrap().len(), 0);
+        let keys: Vec<u32> = cache.cache().iter_active().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [1, 3]);
+
+        // Records written after the snapshot are still replayed
+        cache.insert(4, "four".to_string()).unwrap();
+        drop(cache);
+        let cache: JournaledCacher<u32, String> =
+            JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+        assert_eq!(cache.journal_len(), 1);
+        assert_eq!(cache.cache().len(), 3);
+
+        // A smaller size limit trims the restored entries, oldest first
+        drop(cache);
+        let cache: JournaledCacher<u32, String> =
+            JournaledCacher::open_with_max_size(&path, Duration::from_secs(60), 2).unwrap();
+        let keys: Vec<u32> = cache.cache().iter_active().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [3, 4]);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&journal_path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_rejected_insert() {
+        let path = std::env::temp_dir().join(format!(
+            "simple_cacher_journal_rejected_{}.snap",
+            std::process::id()
+        ));
+
+        let mut cache =
+            JournaledCacher::open_with_max_size(&path, Duration::from_secs(60), 0).unwrap();
+        let err = cache.insert(1u32, "one".to_string()).unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<SimpleCacheError>(),
+            Some(&SimpleCacheError::CapacityRejected { max_size: 0 })
+        );
+        assert!(cache
+            .insert_with_ttl(2, "two".to_string(), Duration::from_secs(1))
+            .is_err());
+        assert_eq!(cache.journal_len(), 0);
+
+        std::fs::remove_file(cache.journal_path()).unwrap();
+    }
+
+    #[test]
+    fn test_journal_torn_final_frame() {
+        let path = std::env::temp_dir().join(format!(
+            "simple_cacher_journal_torn_{}.snap",
+            std::process::id()
+        ));
+
+        let (journal_path, intact) = {
+            let mut cache = JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+            cache.insert(1u32, "one".to_string()).unwrap();
+            let intact = std::fs::metadata(cache.journal_path()).unwrap().len();
+            cache.insert(2, "two".to_string()).unwrap();
+            (cache.journal_path().to_path_buf(), intact as usize)
+        };
+        let bytes = std::fs::read(&journal_path).unwrap();
+
+        // A crash part way through the last append: a short length header, a
+        // short payload, or a payload that doesn't match its checksum
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0xFF;
+        for torn in [
+            bytes[..intact + 2].to_vec(),
+            bytes[..bytes.len() - 1].to_vec(),
+            corrupted,
+        ] {
+            std::fs::write(&journal_path, &torn).unwrap();
+
+            let mut cache: JournaledCacher<u32, String> =
+                JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+            assert_eq!(cache.journal_len(), 1);
+            assert_eq!(
+                std::fs::metadata(&journal_path).unwrap().len() as usize,
+                intact
+            );
+            assert!(!cache.cache().contains_key(&2));
+
+            // New records follow the last intact one
+            cache.insert(3, "three".to_string()).unwrap();
+            drop(cache);
+            let cache: JournaledCacher<u32, String> =
+                JournaledCacher::open(&path, Duration::from_secs(60)).unwrap();
+            let keys: Vec<u32> = cache.cache().iter_active().map(|(k, _)| *k).collect();
+            assert_eq!(keys, [1, 3]);
+        }
+
+        std::fs::remove_file(&journal_path).unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! ## Key Features
 //!
-//! - **Fast O(1) exact key lookups** using IndexMap
+//! - **Fast O(1) exact key lookups, removals and evictions** with insertion order preserved
 //! - **Custom pattern matching** via the `Matcher<T>` trait
 //! - **Automatic expiration** with configurable TTL per entry
 //! - **Size-limited caches** with FIFO eviction (oldest entries removed first)
@@ -28,10 +28,21 @@
 //! match cache.get(&"user:123".to_string()) {
 //!     Ok(entry) => println!("Found: {}", entry.value()),
 //!     Err(SimpleCacheError::NotFound) => println!("Not found"),
-//!     Err(SimpleCacheError::Expired) => println!("Expired"),
+//!     Err(SimpleCacheError::Expired { .. }) => println!("Expired"),
+//!     Err(err) => println!("Error: {}", err),
 //! }
 //! ```
 //!
+//! ## Optional Features
+//!
+//! - `regex_support` - enables the `regex` dependency for regex-based matchers
+//! - `serde` - implements `Serialize`/`Deserialize` for `SimpleCacher` and
+//!   `SimpleCacheObject`, storing entry creation times as wall-clock timestamps
+//! - `persistence` - adds `SimpleCacher::save_to` and `SimpleCacher::load_from`
+//!   for versioned binary snapshots on disk, `JournaledCacher` for crash-safe
+//!   journaling, and `TieredCacher` for spilling evicted entries to disk
+//!   (implies `serde`)
+//!
 //! ## Custom Matching
 //!
 //! ```rust
@@ -49,28 +60,155 @@
 //! }
 //! ```
 
-use indexmap::IndexMap;
+use std::any::Any;
+use std::hash::Hash;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+mod backend;
+mod deps;
+mod index;
+#[cfg(feature = "persistence")]
+mod journal;
+mod loader;
+#[cfg(feature = "persistence")]
+mod persist;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod store;
+mod tags;
+#[cfg(feature = "persistence")]
+mod tiered;
+
+use deps::DependencyGraph;
+use index::{BTreeKeyIndex, KeyIndex};
+use loader::Source;
+use store::Store;
+use tags::TagIndex;
+
+pub use backend::{CacheBackend, LayeredCacher};
+pub use indexmap::Equivalent;
+#[cfg(feature = "persistence")]
+pub use journal::JournaledCacher;
+pub use loader::{Loader, WriteMode, Writer};
+#[cfg(feature = "persistence")]
+pub use tiered::TieredCacher;
+
 /// Error types returned by cache operations.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Errors wrapping an underlying error compare equal only if they share the
+/// same underlying error.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::time::Duration;
+///
+/// let mut cache = SimpleCacher::new(Duration::from_millis(10));
+/// cache.insert("session", "token");
+/// std::thread::sleep(Duration::from_millis(30));
+///
+/// match cache.get(&"session") {
+///     Err(SimpleCacheError::Expired { expired_for }) => {
+///         assert!(expired_for >= Duration::from_millis(20));
+///     }
+///     other => panic!("unexpected result: {:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum SimpleCacheError {
     /// The requested key was not found in the cache.
     NotFound,
     /// The entry was found but has expired and was automatically removed.
-    Expired,
+    Expired {
+        /// How long ago the entry expired.
+        expired_for: Duration,
+    },
+    /// The cache can't hold any entries, so the value wasn't stored.
+    CapacityRejected {
+        /// The configured size limit.
+        max_size: usize,
+    },
+    /// The entry was missing and the configured `Loader` failed to load it.
+    LoadFailed(Arc<dyn std::error::Error + Send + Sync>),
+    /// The configured `Writer` failed to write the entry through.
+    WriteFailed(Arc<dyn std::error::Error + Send + Sync>),
+    /// An entry couldn't be serialized or deserialized, e.g. when moving it
+    /// between memory and disk.
+    SerializationFailed(Arc<dyn std::error::Error + Send + Sync>),
+    /// A lock guarding the cache was poisoned by a panicking thread.
+    LockPoisoned,
+    /// The key was recorded as missing with `insert_negative`.
+    NegativeHit,
+}
+
+impl PartialEq for SimpleCacheError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SimpleCacheError::NotFound, SimpleCacheError::NotFound) => true,
+            (
+                SimpleCacheError::Expired { expired_for: a },
+                SimpleCacheError::Expired { expired_for: b },
+            ) => a == b,
+            (
+                SimpleCacheError::CapacityRejected { max_size: a },
+                SimpleCacheError::CapacityRejected { max_size: b },
+            ) => a == b,
+            (SimpleCacheError::LoadFailed(a), SimpleCacheError::LoadFailed(b))
+            | (SimpleCacheError::WriteFailed(a), SimpleCacheError::WriteFailed(b))
+            | (
+                SimpleCacheError::SerializationFailed(a),
+                SimpleCacheError::SerializationFailed(b),
+            ) => Arc::ptr_eq(a, b),
+            (SimpleCacheError::LockPoisoned, SimpleCacheError::LockPoisoned) => true,
+            (SimpleCacheError::NegativeHit, SimpleCacheError::NegativeHit) => true,
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Display for SimpleCacheError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SimpleCacheError::NotFound => write!(f, "Cache entry not found"),
-            SimpleCacheError::Expired => write!(f, "Cache entry has expired"),
+            SimpleCacheError::Expired { expired_for } => {
+                write!(f, "Cache entry expired {:?} ago", expired_for)
+            }
+            SimpleCacheError::CapacityRejected { max_size } => {
+                write!(f, "Cache entry rejected by size limit of {}", max_size)
+            }
+            SimpleCacheError::LoadFailed(err) => write!(f, "Failed to load cache entry: {}", err),
+            SimpleCacheError::WriteFailed(err) => {
+                write!(f, "Failed to write cache entry through: {}", err)
+            }
+            SimpleCacheError::SerializationFailed(err) => {
+                write!(f, "Failed to serialize cache entry: {}", err)
+            }
+            SimpleCacheError::LockPoisoned => write!(f, "Cache lock was poisoned"),
+            SimpleCacheError::NegativeHit => write!(f, "Cache entry is known not to exist"),
+        }
+    }
+}
+
+impl std::error::Error for SimpleCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimpleCacheError::LoadFailed(err)
+            | SimpleCacheError::WriteFailed(err)
+            | SimpleCacheError::SerializationFailed(err) => Some(&**err),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for SimpleCacheError {}
+impl<G> From<std::sync::PoisonError<G>> for SimpleCacheError {
+    fn from(_: std::sync::PoisonError<G>) -> Self {
+        SimpleCacheError::LockPoisoned
+    }
+}
 
 /// A cached value with metadata about its creation time and expiration.
 ///
@@ -96,21 +234,87 @@ impl std::error::Error for SimpleCacheError {}
 pub struct SimpleCacheObject<U> {
     created_at: Instant,
     value: U,
-    max_age: Duration,
+    /// Time to live from `created_at`, or `None` if the entry never expires.
+    max_age: Option<Duration>,
+    /// Whether the value has changes that haven't been written yet.
+    dirty: bool,
+    meta: EntryMeta,
+}
+
+/// How an entry got into the cache.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::time::Duration;
+///
+/// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+/// cache.set_loader(|key: &u32| -> std::io::Result<u32> { Ok(key * 10) });
+/// cache.insert(1, 10);
+///
+/// assert_eq!(cache.get(&1).unwrap().source(), EntrySource::Inserted);
+/// assert_eq!(cache.get_or_load(&2).unwrap().source(), EntrySource::Loaded);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EntrySource {
+    /// Inserted directly, with `insert` or one of its variants.
+    Inserted,
+    /// Loaded on a miss by the configured loader or a `LayeredCacher` backend.
+    Loaded,
+    /// Rebuilt from a snapshot or a journal.
+    Restored,
+}
+
+/// Bookkeeping kept alongside each entry, for debugging and custom policies.
+#[derive(Debug, Clone)]
+struct EntryMeta {
+    source: EntrySource,
+    access_count: u64,
+    last_accessed: Option<Instant>,
+    last_modified: Instant,
+    /// Caller-defined data, shared between clones of the entry.
+    attributes: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl EntryMeta {
+    fn new(source: EntrySource, now: Instant) -> Self {
+        Self {
+            source,
+            access_count: 0,
+            last_accessed: None,
+            last_modified: now,
+            attributes: None,
+        }
+    }
 }
 
 impl<U> SimpleCacheObject<U> {
-    /// Creates a new cache object with the given value and maximum age.
-    fn new(value: U, max_age: Duration) -> Self {
+    /// Creates a new cache object with the given value and maximum age, or
+    /// one that never expires if `max_age` is `None`.
+    fn new(value: U, max_age: Option<Duration>) -> Self {
+        Self::with_source(value, max_age, EntrySource::Inserted)
+    }
+
+    /// Creates a new cache object that came into the cache from `source`.
+    fn with_source(value: U, max_age: Option<Duration>, source: EntrySource) -> Self {
+        let now = Instant::now();
         Self {
-            created_at: Instant::now(),
+            created_at: now,
             value,
             max_age,
+            dirty: false,
+            meta: EntryMeta::new(source, now),
         }
     }
 
     /// Returns `true` if this cache entry has expired based on its max age.
     ///
+    /// Entries without a max age, inserted with `insert_without_expiry`, into
+    /// a cache created with `without_expiry`, or passed to `persist`, never
+    /// expire.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -126,7 +330,8 @@ impl<U> SimpleCacheObject<U> {
     /// }
     /// ```
     pub fn is_expired(&self) -> bool {
-        self.created_at.elapsed() > self.max_age
+        self.max_age
+            .is_some_and(|max_age| self.created_at.elapsed() > max_age)
     }
 
     /// Returns a reference to the cached value.
@@ -150,6 +355,9 @@ impl<U> SimpleCacheObject<U> {
 
     /// Returns a mutable reference to the cached value.
     ///
+    /// Counts as a modification for `last_modified`, whether or not the value
+    /// is actually changed.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -165,6 +373,7 @@ impl<U> SimpleCacheObject<U> {
     /// }
     /// ```
     pub fn value_mut(&mut self) -> &mut U {
+        self.meta.last_modified = Instant::now();
         &mut self.value
     }
 
@@ -229,6 +438,189 @@ impl<U> SimpleCacheObject<U> {
     pub fn created_at(&self) -> Instant {
         self.created_at
     }
+
+    /// Returns how long this entry has left before it expires.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Duration)` - Time left to live, zero once the entry has expired
+    /// * `None` - The entry never expires
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut pages = SimpleCacher::new(Duration::from_secs(300));
+    /// pages.insert("/index.html", "<html>...</html>");
+    ///
+    /// let entry = pages.get(&"/index.html").unwrap();
+    /// let header = match entry.ttl_remaining() {
+    ///     Some(ttl) => format!("Cache-Control: max-age={}", ttl.as_secs()),
+    ///     None => "Cache-Control: immutable".to_string(),
+    /// };
+    /// println!("{}", header);
+    ///
+    /// let ttl = entry.ttl_remaining().unwrap();
+    /// assert!(ttl <= Duration::from_secs(300) && ttl > Duration::from_secs(295));
+    /// ```
+    pub fn ttl_remaining(&self) -> Option<Duration> {
+        let max_age = self.max_age?;
+        Some(max_age.saturating_sub(self.created_at.elapsed()))
+    }
+
+    /// Returns the instant at which this entry expires.
+    ///
+    /// The instant is in the past for entries that have already expired.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Instant)` - When the entry expires
+    /// * `None` - The entry never expires, or its TTL is too long for the
+    ///   expiry time to be represented as an `Instant`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("key", "value");
+    /// cache.insert_without_expiry("pinned", "value");
+    ///
+    /// let entry = cache.get(&"key").unwrap();
+    /// assert_eq!(entry.expires_at(), Some(entry.created_at() + Duration::from_secs(60)));
+    /// assert_eq!(cache.get(&"pinned").unwrap().expires_at(), None);
+    /// ```
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.created_at.checked_add(self.max_age?)
+    }
+
+    /// Returns how this entry got into the cache.
+    ///
+    /// Entries moved between the tiers of a `TieredCacher` keep their source.
+    pub fn source(&self) -> EntrySource {
+        self.meta.source
+    }
+
+    /// Returns how many times this entry has been returned by a key lookup.
+    ///
+    /// `get`, `get_mut`, `get_or_take_expired`, `get_many` and their
+    /// `_or_load` variants count as accesses; matcher, tag and range queries
+    /// don't. Replacing an entry starts the count over.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("key", "value");
+    /// cache.get(&"key").unwrap();
+    ///
+    /// let entry = cache.get(&"key").unwrap();
+    /// assert_eq!(entry.access_count(), 2);
+    /// assert!(entry.last_accessed().is_some());
+    /// ```
+    pub fn access_count(&self) -> u64 {
+        self.meta.access_count
+    }
+
+    /// Returns when this entry was last returned by a key lookup, or `None`
+    /// if it never has been. See `access_count` for what counts as an access.
+    pub fn last_accessed(&self) -> Option<Instant> {
+        self.meta.last_accessed
+    }
+
+    /// Returns when the value was last set or borrowed with `value_mut`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("counter", 0u32);
+    ///
+    /// let entry = cache.get_mut(&"counter").unwrap();
+    /// assert_eq!(entry.last_modified(), entry.created_at());
+    /// *entry.value_mut() += 1;
+    /// assert!(entry.last_modified() >= entry.created_at());
+    /// ```
+    pub fn last_modified(&self) -> Instant {
+        self.meta.last_modified
+    }
+
+    /// Returns the caller-defined metadata attached to this entry, if it is
+    /// of type `M`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum Priority {
+    ///     Low,
+    ///     High,
+    /// }
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("report", "...");
+    /// cache.get_mut(&"report").unwrap().set_metadata(Priority::High);
+    ///
+    /// let entry = cache.get(&"report").unwrap();
+    /// assert_eq!(entry.metadata::<Priority>(), Some(&Priority::High));
+    /// assert_eq!(entry.metadata::<String>(), None);
+    /// ```
+    pub fn metadata<M>(&self) -> Option<&M>
+    where
+        M: Any,
+    {
+        self.meta.attributes.as_ref()?.downcast_ref()
+    }
+
+    /// Attaches caller-defined metadata to this entry, replacing any that was
+    /// attached before.
+    ///
+    /// Metadata stays with the entry until it is replaced or removed. It is
+    /// not serialized, so entries restored from a snapshot or journal have
+    /// none.
+    pub fn set_metadata<M>(&mut self, metadata: M)
+    where
+        M: Any + Send + Sync,
+    {
+        self.meta.attributes = Some(Arc::new(metadata));
+    }
+
+    /// Removes the caller-defined metadata from this entry.
+    pub fn clear_metadata(&mut self) {
+        self.meta.attributes = None;
+    }
+
+    /// Records that this entry was returned by a key lookup.
+    fn record_access(&mut self) {
+        self.meta.access_count = self.meta.access_count.saturating_add(1);
+        self.meta.last_accessed = Some(Instant::now());
+    }
+
+    /// Returns how long this entry has left before it expires, or
+    /// `Duration::MAX` if it never does.
+    fn time_left(&self) -> Duration {
+        self.ttl_remaining().unwrap_or(Duration::MAX)
+    }
+
+    /// Returns how long ago this entry expired, or zero if it hasn't.
+    fn expired_for(&self) -> Duration {
+        self.max_age.map_or(Duration::ZERO, |max_age| {
+            self.created_at.elapsed().saturating_sub(max_age)
+        })
+    }
 }
 
 /// Trait for implementing custom matching logic against cache keys.
@@ -274,17 +666,30 @@ pub trait Matcher<T> {
     ///
     /// `true` if the key matches, `false` otherwise
     fn matches(&self, key: &T) -> bool;
+
+    /// Returns the range of keys this matcher can possibly match, if known.
+    ///
+    /// Caches with a key index (see `SimpleCacher::enable_key_index`) use this
+    /// hint to only test keys within the returned bounds instead of scanning
+    /// every entry. Every key accepted by `matches` must lie within the bounds;
+    /// keys inside the bounds are still checked with `matches`.
+    ///
+    /// The default implementation returns `None`, meaning no hint is available.
+    fn key_range(&self) -> Option<(Bound<&T>, Bound<&T>)> {
+        None
+    }
 }
 
 /// A high-performance cache with automatic expiration and custom matching capabilities.
 ///
-/// `SimpleCacher` provides fast O(1) exact key lookups using an IndexMap, along with
+/// `SimpleCacher` provides fast O(1) exact key lookups and removals, along with
 /// flexible O(n) pattern matching via the `Matcher` trait. Entries automatically expire
 /// based on configurable TTL values, and the cache can be size-limited with FIFO eviction.
 ///
 /// # Type Parameters
 ///
-/// * `T` - The type of keys (must implement `Clone + Eq + Hash`)
+/// * `T` - The type of keys (must implement `Eq + Hash`; tags, dependencies and
+///   the key index also need `Clone`)
 /// * `U` - The type of cached values
 ///
 /// # Examples
@@ -304,7 +709,8 @@ pub trait Matcher<T> {
 /// match cache.get(&"user:123".to_string()) {
 ///     Ok(entry) => println!("Found: {}", entry.value()),
 ///     Err(SimpleCacheError::NotFound) => println!("Not found"),
-///     Err(SimpleCacheError::Expired) => println!("Expired and removed"),
+///     Err(SimpleCacheError::Expired { .. }) => println!("Expired and removed"),
+///     Err(err) => println!("Error: {}", err),
 /// }
 /// ```
 ///
@@ -326,14 +732,30 @@ pub trait Matcher<T> {
 /// ```
 #[derive(Debug, Clone)]
 pub struct SimpleCacher<T, U> {
-    cache: IndexMap<T, SimpleCacheObject<U>>,
-    max_age: Duration,
+    cache: Store<T, SimpleCacheObject<U>>,
+    /// Default TTL for new entries, or `None` if they never expire.
+    max_age: Option<Duration>,
     max_size: Option<usize>,
+    key_index: Option<Box<dyn KeyIndex<T>>>,
+    tags: TagIndex<T>,
+    deps: DependencyGraph<T>,
+    source: Source<T, U>,
+    negatives: Store<T, SimpleCacheObject<()>>,
+    /// Limit on negative entries, or `None` to use `max_size`.
+    max_negative_size: Option<usize>,
 }
 
+/// Entries removed to make room for an insert, oldest first.
+///
+/// Returned by `SimpleCacher::insert_returning_evicted`.
+pub type Evicted<T, U> = Vec<(T, SimpleCacheObject<U>)>;
+
+/// The entry replaced by an insert, if it was live, and the entries evicted for it.
+type Stored<T, U> = (Option<SimpleCacheObject<U>>, Evicted<T, U>);
+
 impl<T, U> SimpleCacher<T, U>
 where
-    T: Clone + Eq + std::hash::Hash,
+    T: Eq + std::hash::Hash,
 {
     /// Creates a new cache with the specified maximum age for entries.
     ///
@@ -354,17 +776,39 @@ where
     /// cache.insert("key".to_string(), "value".to_string());
     /// ```
     pub fn new(max_age: Duration) -> Self {
-        Self {
-            cache: IndexMap::new(),
-            max_age,
-            max_size: None,
-        }
+        Self::with_limits(Some(max_age), None)
+    }
+
+    /// Creates a new cache whose entries never expire by default.
+    ///
+    /// Entries stay cached until they are removed, replaced or evicted.
+    /// Individual entries can still be given a TTL with `insert_with_ttl`,
+    /// `set_ttl` or `expire_at`. Prefer this over a default TTL of
+    /// `Duration::MAX`, which can overflow when added to an `Instant`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut config = SimpleCacher::without_expiry();
+    /// config.insert("region", "eu-west-1");
+    /// config.insert_with_ttl("token", "abc123", Duration::from_secs(60));
+    ///
+    /// assert!(!config.get(&"region").unwrap().is_expired());
+    /// assert_eq!(config.stats().max_age, None);
+    /// assert_eq!(config.stats().persistent_entries, 1);
+    /// ```
+    pub fn without_expiry() -> Self {
+        Self::with_limits(None, None)
     }
 
     /// Creates a new cache with both maximum age and maximum size constraints.
     ///
     /// When the cache exceeds `max_size` entries, the oldest entries are automatically
-    /// removed to make room for new ones (FIFO eviction policy).
+    /// removed to make room for new ones (FIFO eviction policy). A cache with a
+    /// `max_size` of zero stores nothing and rejects every insert.
     ///
     /// # Arguments
     ///
@@ -383,17 +827,54 @@ where
     /// );
     /// ```
     pub fn with_max_size(max_age: Duration, max_size: usize) -> Self {
+        Self::with_limits(Some(max_age), Some(max_size))
+    }
+
+    /// Creates a size-limited cache whose entries never expire by default.
+    ///
+    /// Entries are only removed by eviction when the cache is full, or
+    /// explicitly. See `without_expiry` and `with_max_size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - Maximum number of entries to keep in the cache
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    ///
+    /// let mut lru_ish = SimpleCacher::with_max_size_without_expiry(2);
+    /// lru_ish.insert(1, "one");
+    /// lru_ish.insert(2, "two");
+    /// lru_ish.insert(3, "three");
+    ///
+    /// assert_eq!(lru_ish.len(), 2);
+    /// assert!(!lru_ish.contains_key(&1));
+    /// ```
+    pub fn with_max_size_without_expiry(max_size: usize) -> Self {
+        Self::with_limits(None, Some(max_size))
+    }
+
+    fn with_limits(max_age: Option<Duration>, max_size: Option<usize>) -> Self {
         Self {
-            cache: IndexMap::new(),
+            cache: Store::new(),
             max_age,
-            max_size: Some(max_size),
+            max_size,
+            key_index: None,
+            tags: TagIndex::default(),
+            deps: DependencyGraph::default(),
+            source: Source::default(),
+            negatives: Store::new(),
+            max_negative_size: None,
         }
     }
 
     /// Retrieves an entry by exact key match in O(1) time.
     ///
     /// If the entry exists but has expired, it will be automatically removed
-    /// from the cache and `SimpleCacheError::Expired` will be returned.
+    /// from the cache and `SimpleCacheError::Expired` will be returned, saying
+    /// how long ago the entry expired.
     ///
     /// # Arguments
     ///
@@ -403,7 +884,13 @@ where
     ///
     /// * `Ok(&SimpleCacheObject<U>)` - The cached entry if found and not expired
     /// * `Err(SimpleCacheError::NotFound)` - The key doesn't exist
-    /// * `Err(SimpleCacheError::Expired)` - The entry existed but expired (now removed)
+    /// * `Err(SimpleCacheError::Expired { .. })` - The entry existed but expired (now removed)
+    /// * `Err(SimpleCacheError::NegativeHit)` - The key was recorded as missing
+    ///   with `insert_negative`
+    ///
+    /// The key can be any borrowed form of `T`, such as `&str` for `String`
+    /// keys. The loader is never called; use `get_or_load` to read through
+    /// to it.
     ///
     /// # Examples
     ///
@@ -420,29 +907,29 @@ where
     ///         println!("Age: {:?}", entry.age());
     ///     }
     ///     Err(SimpleCacheError::NotFound) => println!("User not found"),
-    ///     Err(SimpleCacheError::Expired) => println!("User data expired"),
+    ///     Err(SimpleCacheError::Expired { .. }) => println!("User data expired"),
+    ///     Err(err) => println!("Lookup failed: {}", err),
     /// }
+    ///
+    /// // No allocation needed for the lookup
+    /// assert!(cache.get("user:123").is_ok());
     /// ```
-    pub fn get(&mut self, key: &T) -> Result<&SimpleCacheObject<U>, SimpleCacheError> {
-        // Check if entry exists and if it's expired
-        let should_remove = match self.cache.get(key) {
-            Some(obj) => obj.is_expired(),
-            None => return Err(SimpleCacheError::NotFound),
-        };
-
-        if should_remove {
-            self.cache.shift_remove(key);
-            return Err(SimpleCacheError::Expired);
-        }
+    pub fn get<Q>(&mut self, key: &Q) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.ensure_live(key, |_, miss| Err(miss))?;
 
-        // Safe to get immutable reference now
-        Ok(self.cache.get(key).unwrap())
+        let obj = self.cache.get_mut(key).unwrap();
+        obj.record_access();
+        Ok(obj)
     }
 
-    /// Retrieves a mutable reference to an entry by exact key match.
+    /// Retrieves an entry, loading it if it is missing or expired.
     ///
-    /// Similar to `get()`, but returns a mutable reference that allows you to modify
-    /// the cached value in place. Expired entries are automatically removed.
+    /// Behaves like `get`, except that a missing or expired entry is loaded
+    /// with the configured loader (see `set_loader`) and cached with the
+    /// default TTL. The key is only cloned when a loaded value is cached.
     ///
     /// # Arguments
     ///
@@ -450,9 +937,11 @@ where
     ///
     /// # Returns
     ///
-    /// * `Ok(&mut SimpleCacheObject<U>)` - Mutable reference to the cached entry
-    /// * `Err(SimpleCacheError::NotFound)` - The key doesn't exist
-    /// * `Err(SimpleCacheError::Expired)` - The entry existed but expired (now removed)
+    /// * `Ok(&SimpleCacheObject<U>)` - The cached or freshly loaded entry
+    /// * `Err(SimpleCacheError::LoadFailed)` - The configured loader failed
+    /// * `Err(SimpleCacheError::CapacityRejected)` - A loaded value couldn't be cached
+    /// * `Err(SimpleCacheError)` - No loader is configured or it had no value;
+    ///   see `get`
     ///
     /// # Examples
     ///
@@ -461,43 +950,39 @@ where
     /// use std::time::Duration;
     ///
     /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
-    /// cache.insert("counter".to_string(), 0u32);
+    /// cache.set_loader(|id: &u32| -> std::io::Result<u32> { Ok(id * 10) });
     ///
-    /// if let Ok(entry) = cache.get_mut(&"counter".to_string()) {
-    ///     *entry.value_mut() += 1;
-    ///     assert_eq!(*entry.value(), 1);
-    /// }
+    /// assert_eq!(*cache.get_or_load(&4).unwrap().value(), 40);
+    /// assert!(cache.get(&4).is_ok());
     /// ```
-    pub fn get_mut(&mut self, key: &T) -> Result<&mut SimpleCacheObject<U>, SimpleCacheError> {
-        // Check if exists and if it's expired first
-        let should_remove = match self.cache.get(key) {
-            Some(obj) => obj.is_expired(),
-            None => return Err(SimpleCacheError::NotFound),
-        };
-
-        if should_remove {
-            self.cache.shift_remove(key);
-            return Err(SimpleCacheError::Expired);
-        }
+    pub fn get_or_load(&mut self, key: &T) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    where
+        T: Clone,
+    {
+        self.ensure_live(key, |cache, miss| cache.load_missing(key, miss))?;
 
-        // Safe to get mutable reference now
-        Ok(self.cache.get_mut(key).unwrap())
+        let obj = self.cache.get_mut(key).unwrap();
+        obj.record_access();
+        Ok(obj)
     }
 
-    /// Finds the first entry matching the given matcher in O(n) time.
+    /// Retrieves a mutable reference to an entry by exact key match.
     ///
-    /// This method iterates through all cache entries and returns the first one
-    /// that matches the provided matcher's criteria. Expired entries encountered
-    /// during the search are automatically cleaned up.
+    /// Similar to `get()`, but returns a mutable reference that allows you to modify
+    /// the cached value in place. Expired entries are automatically removed. In
+    /// `WriteMode::Behind`, the entry is marked dirty so the modified value is
+    /// written on the next flush.
     ///
     /// # Arguments
     ///
-    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `key` - The key to look up
     ///
     /// # Returns
     ///
-    /// * `Ok(&SimpleCacheObject<U>)` - The first matching entry found
-    /// * `Err(SimpleCacheError::NotFound)` - No matching entries found
+    /// * `Ok(&mut SimpleCacheObject<U>)` - Mutable reference to the cached entry
+    /// * `Err(SimpleCacheError::NotFound)` - The key doesn't exist
+    /// * `Err(SimpleCacheError::Expired { .. })` - The entry existed but expired (now removed)
+    /// * `Err(SimpleCacheError::NegativeHit)` - The key was recorded as missing
     ///
     /// # Examples
     ///
@@ -505,61 +990,35 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("user:alice".to_string(), "Alice".to_string());
-    /// cache.insert("user:bob".to_string(), "Bob".to_string());
-    /// cache.insert("admin:charlie".to_string(), "Charlie".to_string());
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("counter".to_string(), 0u32);
     ///
-    /// let user_matcher = PrefixMatcher::new("user:");
-    /// if let Ok(user) = cache.get_by_matcher(&user_matcher) {
-    ///     println!("Found user: {}", user.value());
+    /// if let Ok(entry) = cache.get_mut("counter") {
+    ///     *entry.value_mut() += 1;
+    ///     assert_eq!(*entry.value(), 1);
     /// }
     /// ```
-    pub fn get_by_matcher<M>(
-        &mut self,
-        matcher: &M,
-    ) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Result<&mut SimpleCacheObject<U>, SimpleCacheError>
     where
-        M: Matcher<T>,
+        Q: ?Sized + Hash + Equivalent<T>,
     {
-        // First pass: collect expired keys and find match
-        let mut expired_keys = Vec::new();
-        let mut found_key = None;
-
-        for (key, obj) in &self.cache {
-            if obj.is_expired() {
-                expired_keys.push(key.clone());
-            } else if found_key.is_none() && matcher.matches(key) {
-                found_key = Some(key.clone());
-            }
-        }
-
-        // Clean up expired entries
-        for key in expired_keys {
-            self.cache.shift_remove(&key);
-        }
-
-        // Return the found entry (get fresh reference after cleanup)
-        if let Some(key) = found_key {
-            self.cache.get(&key).ok_or(SimpleCacheError::NotFound)
-        } else {
-            Err(SimpleCacheError::NotFound)
-        }
+        self.ensure_live(key, |_, miss| Err(miss))?;
+        Ok(self.modify_live(key))
     }
 
-    /// Finds all entries matching the given matcher.
+    /// Retrieves a mutable reference to an entry, loading it if it is
+    /// missing or expired.
     ///
-    /// This method returns all cache entries that match the provided matcher's criteria.
-    /// The cache is automatically cleaned of expired entries before searching.
+    /// Combines `get_mut` with the loading of `get_or_load`.
     ///
     /// # Arguments
     ///
-    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `key` - The key to look up
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing references to matching keys and their cached values.
-    /// The vector may be empty if no matches are found.
+    /// * `Ok(&mut SimpleCacheObject<U>)` - Mutable reference to the cached or loaded entry
+    /// * `Err(SimpleCacheError)` - The entry couldn't be loaded; see `get_or_load`
     ///
     /// # Examples
     ///
@@ -567,38 +1026,44 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("user:alice".to_string(), "Alice".to_string());
-    /// cache.insert("user:bob".to_string(), "Bob".to_string());
-    /// cache.insert("admin:charlie".to_string(), "Charlie".to_string());
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.set_loader(|_: &&str| -> std::io::Result<u32> { Ok(0) });
     ///
-    /// let user_matcher = PrefixMatcher::new("user:");
-    /// let users = cache.get_all_by_matcher(&user_matcher);
-    /// println!("Found {} users", users.len());
+    /// *cache.get_mut_or_load(&"visits").unwrap().value_mut() += 1;
+    /// assert_eq!(*cache.get(&"visits").unwrap().value(), 1);
     /// ```
-    pub fn get_all_by_matcher<M>(&mut self, matcher: &M) -> Vec<(&T, &SimpleCacheObject<U>)>
+    pub fn get_mut_or_load(
+        &mut self,
+        key: &T,
+    ) -> Result<&mut SimpleCacheObject<U>, SimpleCacheError>
     where
-        M: Matcher<T>,
+        T: Clone,
     {
-        // Clean up expired entries first
-        self.cleanup_expired();
-
-        self.cache
-            .iter()
-            .filter(|(key, obj)| !obj.is_expired() && matcher.matches(key))
-            .collect()
+        self.ensure_live(key, |cache, miss| cache.load_missing(key, miss))?;
+        Ok(self.modify_live(key))
     }
 
-    /// Inserts a new entry into the cache with the default TTL.
+    /// Retrieves an entry, handing back its value even if it has expired.
     ///
-    /// If the cache has a size limit and is at capacity, the oldest entry
-    /// will be automatically removed to make room for the new entry (FIFO eviction).
-    /// If an entry with the same key already exists, it will be replaced.
+    /// Fresh entries are returned as with `get`. An expired entry is removed
+    /// from the cache (dependents are kept) and returned as `MaybeStale::Stale`,
+    /// so callers can fall back to stale data when the origin is unavailable.
+    /// Use `get_or_load_or_take_expired` to try the loader first.
+    ///
+    /// In `WriteMode::Behind`, unwritten changes to the expired entry are not
+    /// lost: they are written before the stale value is handed back.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key to associate with the value
-    /// * `value` - The value to cache
+    /// * `key` - The key to look up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MaybeStale::Fresh(&SimpleCacheObject<U>))` - A live entry
+    /// * `Ok(MaybeStale::Stale(SimpleCacheObject<U>))` - The expired entry, now removed
+    /// * `Err(SimpleCacheError::WriteFailed)` - The stale entry had unwritten
+    ///   changes that couldn't be written; they stay queued for the next flush
+    /// * `Err(SimpleCacheError)` - The key is missing; see `get`
     ///
     /// # Examples
     ///
@@ -606,31 +1071,84 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("user:123".to_string(), "Alice Johnson".to_string());
+    /// let mut config = SimpleCacher::new(Duration::from_millis(10));
+    /// config.insert("feature_flags", "dark_mode=on");
+    /// std::thread::sleep(Duration::from_millis(20));
+    ///
+    /// match config.get_or_take_expired(&"feature_flags") {
+    ///     Ok(MaybeStale::Fresh(entry)) => println!("fresh: {}", entry.value()),
+    ///     Ok(MaybeStale::Stale(entry)) => println!("origin down, using: {}", entry.value()),
+    ///     Err(err) => println!("no config: {}", err),
+    /// }
+    /// assert!(config.is_empty());
     /// ```
-    pub fn insert(&mut self, key: T, value: U) {
-        // Enforce max size by removing oldest entries (FIFO)
-        if let Some(max_size) = self.max_size {
-            while self.cache.len() >= max_size {
-                self.cache.shift_remove_index(0);
-            }
-        }
+    pub fn get_or_take_expired<Q>(&mut self, key: &Q) -> Result<MaybeStale<'_, U>, SimpleCacheError>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.take_expired_with(key, |_, miss| Err(miss))
+    }
 
-        let cache_obj = SimpleCacheObject::new(value, self.max_age);
-        self.cache.insert(key, cache_obj);
+    /// Retrieves an entry, loading it if it is missing or expired and
+    /// falling back to the expired value if loading fails.
+    ///
+    /// Behaves like `get_or_take_expired`, but the loader is tried before an
+    /// expired entry is handed back. If a loaded value replaces the expired
+    /// entry, its unwritten changes are kept for the next flush.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to look up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MaybeStale::Fresh(&SimpleCacheObject<U>))` - A live or freshly loaded entry
+    /// * `Ok(MaybeStale::Stale(SimpleCacheObject<U>))` - The expired entry, now removed
+    /// * `Err(SimpleCacheError)` - The key is missing and couldn't be loaded,
+    ///   or the stale entry couldn't be written; see `get_or_take_expired`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let mut config = SimpleCacher::new(Duration::from_millis(10));
+    /// config.insert("feature_flags", "dark_mode=on");
+    /// config.set_loader(|_: &&str| -> io::Result<&'static str> {
+    ///     Err(io::Error::new(io::ErrorKind::Other, "origin down"))
+    /// });
+    /// std::thread::sleep(Duration::from_millis(20));
+    ///
+    /// let entry = config.get_or_load_or_take_expired(&"feature_flags").unwrap();
+    /// assert!(entry.is_stale());
+    /// assert_eq!(*entry.value(), "dark_mode=on");
+    /// ```
+    pub fn get_or_load_or_take_expired(
+        &mut self,
+        key: &T,
+    ) -> Result<MaybeStale<'_, U>, SimpleCacheError>
+    where
+        T: Clone,
+    {
+        self.take_expired_with(key, |cache, miss| cache.load_missing(key, miss))
     }
 
-    /// Inserts a new entry into the cache with a custom TTL.
+    /// Retrieves several entries at once.
     ///
-    /// This allows you to override the default TTL for specific entries,
-    /// useful for caching data with different freshness requirements.
+    /// Behaves like calling `get` for every key, but all expired entries
+    /// among the keys are removed in a single pass over the cache instead of
+    /// one removal per key.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key to associate with the value
-    /// * `value` - The value to cache
-    /// * `ttl` - Custom time-to-live for this specific entry
+    /// * `keys` - The keys to look up, or any borrowed form of them
+    ///
+    /// # Returns
+    ///
+    /// One result per key, in the order of `keys`, with the same meaning as
+    /// the result of `get`.
     ///
     /// # Examples
     ///
@@ -638,38 +1156,87 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300)); // Default 5 min
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("user:1".to_string(), "Alice");
+    /// cache.insert("user:2".to_string(), "Bob");
     ///
-    /// // Cache with custom 1-hour TTL
-    /// cache.insert_with_ttl(
-    ///     "important_data".to_string(),
-    ///     "critical information".to_string(),
-    ///     Duration::from_secs(3600)
-    /// );
+    /// let results = cache.get_many(["user:1", "user:3", "user:2"]);
+    /// assert_eq!(*results[0].as_ref().unwrap().value(), "Alice");
+    /// assert_eq!(results[1].as_ref().unwrap_err(), &SimpleCacheError::NotFound);
+    /// assert_eq!(*results[2].as_ref().unwrap().value(), "Bob");
     /// ```
-    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) {
-        if let Some(max_size) = self.max_size {
-            while self.cache.len() >= max_size {
-                self.cache.shift_remove_index(0);
-            }
-        }
+    pub fn get_many<'q, Q, I>(
+        &mut self,
+        keys: I,
+    ) -> Vec<Result<&SimpleCacheObject<U>, SimpleCacheError>>
+    where
+        I: IntoIterator<Item = &'q Q>,
+        Q: 'q + ?Sized + Hash + Equivalent<T>,
+    {
+        self.get_many_with(keys, |_, _, miss| Err(miss))
+    }
 
-        let cache_obj = SimpleCacheObject::new(value, ttl);
-        self.cache.insert(key, cache_obj);
+    /// Retrieves several entries at once, loading the missing and expired
+    /// ones.
+    ///
+    /// Behaves like `get_many`, except that misses are loaded as with
+    /// `get_or_load`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys to look up
+    ///
+    /// # Returns
+    ///
+    /// One result per key, in the order of `keys`, with the same meaning as
+    /// the result of `get_or_load`. If loading several misses overflows the
+    /// size limit, entries loaded earlier in the batch may have been evicted
+    /// again and are reported as `NotFound`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert(1, 100);
+    /// cache.set_loader(|id: &u32| -> std::io::Result<u32> { Ok(id * 10) });
+    ///
+    /// let results = cache.get_many_or_load([&1, &2]);
+    /// assert_eq!(*results[0].as_ref().unwrap().value(), 100);
+    /// assert_eq!(*results[1].as_ref().unwrap().value(), 20);
+    /// ```
+    pub fn get_many_or_load<'q, I>(
+        &mut self,
+        keys: I,
+    ) -> Vec<Result<&SimpleCacheObject<U>, SimpleCacheError>>
+    where
+        I: IntoIterator<Item = &'q T>,
+        T: 'q + Clone,
+    {
+        self.get_many_with(keys, |cache, key, miss| cache.load_missing(key, miss))
     }
 
-    /// Removes an entry by key and returns it if it existed.
+    /// Finds the first entry matching the given matcher in O(n) time.
     ///
-    /// This method removes the entry regardless of whether it has expired.
-    /// Returns `None` if the key doesn't exist.
+    /// This method iterates through all cache entries and returns the first one
+    /// in insertion order that matches the provided matcher's criteria. Expired
+    /// entries encountered during the search are automatically cleaned up. Use
+    /// `get_by_matcher_ordered` to pick the match by age or remaining TTL instead.
+    ///
+    /// If the key index is enabled and the matcher provides a `key_range` hint,
+    /// only keys within that range are visited and the first match in key order
+    /// is returned.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key of the entry to remove
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
     ///
     /// # Returns
     ///
-    /// `Some(SimpleCacheObject<U>)` if the key existed, `None` otherwise
+    /// * `Ok(&SimpleCacheObject<U>)` - The first matching entry found
+    /// * `Err(SimpleCacheError::NotFound)` - No matching entries found
     ///
     /// # Examples
     ///
@@ -678,27 +1245,58 @@ where
     /// use std::time::Duration;
     ///
     /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("temp_data".to_string(), "temporary".to_string());
+    /// cache.insert("user:alice".to_string(), "Alice".to_string());
+    /// cache.insert("user:bob".to_string(), "Bob".to_string());
+    /// cache.insert("admin:charlie".to_string(), "Charlie".to_string());
     ///
-    /// if let Some(removed) = cache.remove(&"temp_data".to_string()) {
-    ///     println!("Removed: {}", removed.into_value());
+    /// let user_matcher = PrefixMatcher::new("user:");
+    /// if let Ok(user) = cache.get_by_matcher(&user_matcher) {
+    ///     println!("Found user: {}", user.value());
     /// }
     /// ```
-    pub fn remove(&mut self, key: &T) -> Option<SimpleCacheObject<U>> {
-        self.cache.shift_remove(key)
+    pub fn get_by_matcher<M>(
+        &mut self,
+        matcher: &M,
+    ) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    where
+        M: Matcher<T>,
+    {
+        let found = match self.find_indexed(matcher) {
+            Some(found) => found,
+            None => {
+                // Clean up expired entries, then find the first match
+                self.cleanup_expired();
+                self.cache
+                    .iter_full()
+                    .find(|(_, key, _)| matcher.matches(key))
+                    .map(|(slot, _, _)| slot)
+            }
+        };
+
+        found
+            .and_then(|slot| self.cache.get_slot(slot))
+            .map(|(_, obj)| obj)
+            .ok_or(SimpleCacheError::NotFound)
     }
 
-    /// Checks if a key exists in the cache and is not expired.
+    /// Finds all entries matching the given matcher.
     ///
-    /// This is a lightweight check that doesn't trigger cleanup of expired entries.
+    /// This method returns all cache entries that match the provided matcher's criteria.
+    /// The cache is automatically cleaned of expired entries before searching.
+    ///
+    /// If the key index is enabled and the matcher provides a `key_range` hint,
+    /// only keys within that range are visited, expired entries are cleaned up
+    /// only among those keys, and results are returned in key order. Otherwise
+    /// results are returned in insertion order.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key to check for existence
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
     ///
     /// # Returns
     ///
-    /// `true` if the key exists and is not expired, `false` otherwise
+    /// A vector of tuples containing references to matching keys and their cached values.
+    /// The vector may be empty if no matches are found.
     ///
     /// # Examples
     ///
@@ -707,27 +1305,136 @@ where
     /// use std::time::Duration;
     ///
     /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("key".to_string(), "value".to_string());
+    /// cache.insert("user:alice".to_string(), "Alice".to_string());
+    /// cache.insert("user:bob".to_string(), "Bob".to_string());
+    /// cache.insert("admin:charlie".to_string(), "Charlie".to_string());
     ///
-    /// assert!(cache.contains_key(&"key".to_string()));
-    /// assert!(!cache.contains_key(&"nonexistent".to_string()));
+    /// let user_matcher = PrefixMatcher::new("user:");
+    /// let users = cache.get_all_by_matcher(&user_matcher);
+    /// println!("Found {} users", users.len());
     /// ```
-    pub fn contains_key(&self, key: &T) -> bool {
-        self.cache
-            .get(key)
-            .map(|obj| !obj.is_expired())
-            .unwrap_or(false)
+    pub fn get_all_by_matcher<M>(&mut self, matcher: &M) -> Vec<(&T, &SimpleCacheObject<U>)>
+    where
+        M: Matcher<T>,
+    {
+        let slots = self.matching_slots(matcher);
+        self.entries_at(slots)
     }
 
-    /// Manually removes all expired entries from the cache.
+    /// Finds all entries matching the given matcher, sorted by `order`.
     ///
-    /// This method performs a full scan of the cache and removes all entries
-    /// that have exceeded their TTL. This can be useful for periodic cleanup
-    /// to free memory and maintain cache performance.
+    /// Works like `get_all_by_matcher`, but the result order is chosen by the
+    /// caller instead of depending on insertion order or the key index. Ties
+    /// keep their insertion order. If `limit` is given, at most that many
+    /// entries are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `order` - How to sort the matching entries
+    /// * `limit` - Maximum number of entries to return, or `None` for all
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_with_ttl("session:a".to_string(), 1, Duration::from_secs(60));
+    /// cache.insert_with_ttl("session:b".to_string(), 2, Duration::from_secs(600));
+    /// cache.insert_with_ttl("session:c".to_string(), 3, Duration::from_secs(30));
+    ///
+    /// let matcher = PrefixMatcher::new("session:");
+    /// let longest = cache.get_all_by_matcher_sorted(&matcher, MatchOrder::LongestTtl, Some(2));
+    /// assert_eq!(longest.len(), 2);
+    /// assert_eq!(longest[0].0, "session:b");
+    /// assert_eq!(longest[1].0, "session:a");
+    /// ```
+    pub fn get_all_by_matcher_sorted<M>(
+        &mut self,
+        matcher: &M,
+        order: MatchOrder,
+        limit: Option<usize>,
+    ) -> Vec<(&T, &SimpleCacheObject<U>)>
+    where
+        M: Matcher<T>,
+    {
+        let mut slots = self.matching_slots(matcher);
+        self.cache.sort_by_order(&mut slots);
+        if order != MatchOrder::Insertion {
+            let cache = &self.cache;
+            slots.sort_by(|&a, &b| {
+                order.compare(cache.get_slot(a).unwrap().1, cache.get_slot(b).unwrap().1)
+            });
+        }
+        if let Some(limit) = limit {
+            slots.truncate(limit);
+        }
+        self.entries_at(slots)
+    }
+
+    /// Finds all entries matching the given matcher, sorted with a comparator.
+    ///
+    /// Like `get_all_by_matcher_sorted`, but with a caller-provided ordering.
+    /// The sort is stable, so entries comparing equal keep their insertion order.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `compare` - Comparator deciding the order of two matching entries
+    /// * `limit` - Maximum number of entries to return, or `None` for all
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("b".to_string(), 2);
+    /// cache.insert("c".to_string(), 3);
+    /// cache.insert("a".to_string(), 1);
+    ///
+    /// let all = FnMatcher::new(|_: &String| true);
+    /// let by_key = cache.get_all_by_matcher_sorted_by(&all, |a, b| a.0.cmp(b.0), None);
+    /// let keys: Vec<&String> = by_key.into_iter().map(|(key, _)| key).collect();
+    /// assert_eq!(keys, ["a", "b", "c"]);
+    /// ```
+    pub fn get_all_by_matcher_sorted_by<M, F>(
+        &mut self,
+        matcher: &M,
+        mut compare: F,
+        limit: Option<usize>,
+    ) -> Vec<(&T, &SimpleCacheObject<U>)>
+    where
+        M: Matcher<T>,
+        F: FnMut(&(&T, &SimpleCacheObject<U>), &(&T, &SimpleCacheObject<U>)) -> std::cmp::Ordering,
+    {
+        let mut slots = self.matching_slots(matcher);
+        self.cache.sort_by_order(&mut slots);
+        let cache = &self.cache;
+        slots.sort_by(|&a, &b| compare(&cache.get_slot(a).unwrap(), &cache.get_slot(b).unwrap()));
+        if let Some(limit) = limit {
+            slots.truncate(limit);
+        }
+        self.entries_at(slots)
+    }
+
+    /// Finds the first entry matching the given matcher according to `order`.
+    ///
+    /// Unlike `get_by_matcher`, the result doesn't depend on insertion order
+    /// or on whether the key index is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `order` - Which matching entry is considered first
     ///
     /// # Returns
     ///
-    /// The number of expired entries that were removed
+    /// * `Ok(&SimpleCacheObject<U>)` - The first matching entry according to `order`
+    /// * `Err(SimpleCacheError::NotFound)` - No matching entries found
     ///
     /// # Examples
     ///
@@ -735,99 +1442,2100 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_millis(100));
-    /// cache.insert("key1".to_string(), "value1".to_string());
-    /// cache.insert("key2".to_string(), "value2".to_string());
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert(5, "five");
+    /// cache.insert(10, "ten");
     ///
-    /// // Wait for expiration
-    /// std::thread::sleep(Duration::from_millis(150));
+    /// let matcher = RangeMatcher::new(3, 12);
+    /// let newest = cache.get_by_matcher_ordered(&matcher, MatchOrder::Newest).unwrap();
+    /// assert_eq!(*newest.value(), "ten");
+    /// ```
+    pub fn get_by_matcher_ordered<M>(
+        &mut self,
+        matcher: &M,
+        order: MatchOrder,
+    ) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    where
+        M: Matcher<T>,
+    {
+        self.get_all_by_matcher_sorted(matcher, order, Some(1))
+            .pop()
+            .map(|(_, obj)| obj)
+            .ok_or(SimpleCacheError::NotFound)
+    }
+
+    /// Finds the first entry matching the given matcher according to a comparator.
     ///
-    /// let removed = cache.cleanup_expired();
-    /// println!("Cleaned up {} expired entries", removed);
+    /// Returns the smallest matching entry according to `compare`, or the
+    /// earliest inserted one if several compare equal.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - An implementation of `Matcher<T>` that defines the search criteria
+    /// * `compare` - Comparator deciding the order of two matching entries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("low".to_string(), 3);
+    /// cache.insert("high".to_string(), 9);
+    ///
+    /// let all = FnMatcher::new(|_: &String| true);
+    /// let largest = cache
+    ///     .get_by_matcher_by(&all, |a, b| b.1.value().cmp(a.1.value()))
+    ///     .unwrap();
+    /// assert_eq!(*largest.value(), 9);
     /// ```
-    pub fn cleanup_expired(&mut self) -> usize {
-        let expired_keys: Vec<T> = self
-            .cache
+    pub fn get_by_matcher_by<M, F>(
+        &mut self,
+        matcher: &M,
+        mut compare: F,
+    ) -> Result<&SimpleCacheObject<U>, SimpleCacheError>
+    where
+        M: Matcher<T>,
+        F: FnMut(&(&T, &SimpleCacheObject<U>), &(&T, &SimpleCacheObject<U>)) -> std::cmp::Ordering,
+    {
+        let mut slots = self.matching_slots(matcher);
+        self.cache.sort_by_order(&mut slots);
+        let cache = &self.cache;
+        slots
+            .into_iter()
+            .map(|slot| cache.get_slot(slot).unwrap())
+            .min_by(|a, b| compare(a, b))
+            .map(|(_, obj)| obj)
+            .ok_or(SimpleCacheError::NotFound)
+    }
+
+    /// Inserts a new entry into the cache with the default TTL.
+    ///
+    /// If the cache has a size limit and is at capacity, the oldest entry
+    /// will be automatically removed to make room for the new entry (FIFO eviction).
+    /// If an entry with the same key already exists, it is replaced in place
+    /// without evicting anything.
+    ///
+    /// If a writer is configured (see `set_writer`), the value is written
+    /// through first and only cached if the write succeeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    ///
+    /// # Returns
+    ///
+    /// The previous value for the key, or `None` if there was no entry or it
+    /// had expired
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached: the cache has a size limit of
+    /// zero, or the configured writer fails in `WriteMode::Through`. Without
+    /// a writer and with a non-zero size limit, `insert` never fails. Use
+    /// `try_insert` to handle these errors instead. In `WriteMode::Behind`, a
+    /// failed flush of other dirty entries leaves them queued for the next
+    /// flush and doesn't panic.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// assert_eq!(cache.insert("user:123".to_string(), "Alice Johnson".to_string()), None);
+    ///
+    /// let previous = cache.insert("user:123".to_string(), "Alice Smith".to_string());
+    /// assert_eq!(previous.as_deref(), Some("Alice Johnson"));
+    /// ```
+    #[track_caller]
+    pub fn insert(&mut self, key: T, value: U) -> Option<U> {
+        let cache_obj = SimpleCacheObject::new(value, self.max_age);
+        self.store_or_panic(key, cache_obj)
+    }
+
+    /// Inserts a new entry with the default TTL, reporting write-through failures.
+    ///
+    /// Behaves like `insert`, except that errors are returned instead of
+    /// panicking, and a failed flush in `WriteMode::Behind` is reported too.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Option<U>)` - The value was written through (if a writer is configured)
+    ///   and cached; holds the previous value as with `insert`
+    /// * `Err(SimpleCacheError::WriteFailed)` - The writer failed. In `WriteMode::Through`
+    ///   the value isn't cached; in `WriteMode::Behind` it is cached and stays dirty
+    /// * `Err(SimpleCacheError::CapacityRejected)` - The cache has a size limit of zero
+    pub fn try_insert(&mut self, key: T, value: U) -> Result<Option<U>, SimpleCacheError> {
+        let cache_obj = SimpleCacheObject::new(value, self.max_age);
+        self.store(key, cache_obj)
+    }
+
+    /// Replaces the value of an existing entry, using the default TTL.
+    ///
+    /// Unlike `insert`, nothing is cached if the key is missing or its entry
+    /// has expired. Otherwise this behaves like `insert`: the entry keeps its
+    /// position in the eviction order, and its tags and dependencies are
+    /// dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry to replace
+    /// * `value` - The new value
+    ///
+    /// # Returns
+    ///
+    /// The previous value, or `None` if nothing was replaced
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new value can't be cached; see `insert`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// assert_eq!(cache.replace("config", "v2"), None);
+    /// assert!(cache.is_empty());
+    ///
+    /// cache.insert("config", "v1");
+    /// assert_eq!(cache.replace("config", "v2"), Some("v1"));
+    /// assert_eq!(*cache.get(&"config").unwrap().value(), "v2");
+    /// ```
+    #[track_caller]
+    pub fn replace(&mut self, key: T, value: U) -> Option<U> {
+        if !self.contains_key(&key) {
+            return None;
+        }
+        self.insert(key, value)
+    }
+
+    /// Inserts a new entry into the cache with a custom TTL.
+    ///
+    /// This allows you to override the default TTL for specific entries,
+    /// useful for caching data with different freshness requirements.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    /// * `ttl` - Custom time-to-live for this specific entry
+    ///
+    /// # Returns
+    ///
+    /// The previous value for the key, as with `insert`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached; see `insert`. Use
+    /// `try_insert_with_ttl` to handle the error instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300)); // Default 5 min
+    ///
+    /// // Cache with custom 1-hour TTL
+    /// cache.insert_with_ttl(
+    ///     "important_data".to_string(),
+    ///     "critical information".to_string(),
+    ///     Duration::from_secs(3600)
+    /// );
+    /// ```
+    #[track_caller]
+    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) -> Option<U> {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.store_or_panic(key, cache_obj)
+    }
+
+    /// Inserts a new entry with a custom TTL, reporting write-through failures.
+    ///
+    /// See `try_insert`.
+    pub fn try_insert_with_ttl(
+        &mut self,
+        key: T,
+        value: U,
+        ttl: Duration,
+    ) -> Result<Option<U>, SimpleCacheError> {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.store(key, cache_obj)
+    }
+
+    /// Inserts a new entry that never expires, whatever the default TTL.
+    ///
+    /// The entry stays cached until it is removed, replaced or evicted, or
+    /// until it is given a TTL with `set_ttl` or `expire_at`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    ///
+    /// # Returns
+    ///
+    /// The previous value for the key, as with `insert`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached; see `insert`. Use
+    /// `try_insert_without_expiry` to handle the error instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_millis(10));
+    /// cache.insert("temporary", 1);
+    /// cache.insert_without_expiry("pinned", 2);
+    ///
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// assert_eq!(cache.cleanup_expired(), 1);
+    /// assert!(cache.contains_key(&"pinned"));
+    /// ```
+    #[track_caller]
+    pub fn insert_without_expiry(&mut self, key: T, value: U) -> Option<U> {
+        let cache_obj = SimpleCacheObject::new(value, None);
+        self.store_or_panic(key, cache_obj)
+    }
+
+    /// Inserts a new entry that never expires, reporting write-through
+    /// failures.
+    ///
+    /// See `try_insert`.
+    pub fn try_insert_without_expiry(
+        &mut self,
+        key: T,
+        value: U,
+    ) -> Result<Option<U>, SimpleCacheError> {
+        let cache_obj = SimpleCacheObject::new(value, None);
+        self.store(key, cache_obj)
+    }
+
+    /// Inserts a new entry with the default TTL and returns the entries
+    /// evicted to make room for it.
+    ///
+    /// This lets callers release resources held by displaced values (file
+    /// handles, connections, ...) right away. Replacing an existing key never
+    /// evicts anything. In `WriteMode::Behind`, evicted entries with unwritten
+    /// changes are written before being returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Evicted<T, U>)` - The evicted entries, oldest first; empty if
+    ///   nothing was evicted
+    /// * `Err(SimpleCacheError::CapacityRejected)` - The cache can't hold any
+    ///   entries; nothing was inserted
+    /// * `Err(SimpleCacheError::WriteFailed)` - Writing the value through
+    ///   failed and nothing was inserted, or (in `WriteMode::Behind`) writing
+    ///   the dirty evicted entries failed. In the latter case the entry was
+    ///   inserted, the unwritten entries stay queued for the next flush and
+    ///   the other evicted entries are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut files = SimpleCacher::with_max_size(Duration::from_secs(60), 2);
+    /// files.insert("a.log", 1);
+    /// files.insert("b.log", 2);
+    ///
+    /// for (path, handle) in files.insert_returning_evicted("c.log", 3).unwrap() {
+    ///     println!("closing {} (handle {})", path, handle.value());
+    /// }
+    /// assert_eq!(files.len(), 2);
+    /// ```
+    pub fn insert_returning_evicted(
+        &mut self,
+        key: T,
+        value: U,
+    ) -> Result<Evicted<T, U>, SimpleCacheError> {
+        let cache_obj = SimpleCacheObject::new(value, self.max_age);
+        self.store_returning_evicted(key, cache_obj)
+    }
+
+    /// Inserts a new entry with a custom TTL and returns the entries evicted
+    /// to make room for it.
+    ///
+    /// See `insert_returning_evicted`.
+    pub fn insert_with_ttl_returning_evicted(
+        &mut self,
+        key: T,
+        value: U,
+        ttl: Duration,
+    ) -> Result<Evicted<T, U>, SimpleCacheError> {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.store_returning_evicted(key, cache_obj)
+    }
+
+    /// Inserts several entries with the default TTL at once.
+    ///
+    /// Behaves like calling `insert` for every entry in order, but the size
+    /// limit is enforced once for the whole batch, and a configured writer
+    /// receives all entries in a single `write_batch` call.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The key/value pairs to cache
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - All entries were written through (if a writer is configured) and cached
+    /// * `Err(SimpleCacheError::WriteFailed)` - The writer failed; nothing was cached
+    /// * `Err(SimpleCacheError::CapacityRejected)` - The cache has a size limit of zero
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 100);
+    /// cache
+    ///     .insert_many((0..150).map(|i| (format!("row:{}", i), i)))
+    ///     .unwrap();
+    ///
+    /// // Only the newest 100 entries are kept
+    /// assert_eq!(cache.len(), 100);
+    /// assert!(cache.contains_key("row:149"));
+    /// ```
+    pub fn insert_many<I>(&mut self, entries: I) -> Result<(), SimpleCacheError>
+    where
+        I: IntoIterator<Item = (T, U)>,
+    {
+        let entries: Vec<(T, U)> = entries.into_iter().collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.check_capacity()?;
+
+        let write_behind = self.source.is_write_behind();
+        if !write_behind {
+            let batch: Vec<(&T, &U)> = entries.iter().map(|(key, value)| (key, value)).collect();
+            self.source
+                .write_batch(&batch)
+                .map_err(|err| SimpleCacheError::WriteFailed(Arc::from(err)))?;
+        }
+
+        for (key, value) in entries {
+            let mut cache_obj = SimpleCacheObject::new(value, self.max_age);
+            cache_obj.dirty = write_behind;
+            self.invalidate_dependents(&key);
+            self.place_object(key, cache_obj);
+        }
+
+        if let Some(max_size) = self.max_size {
+            for (key, obj) in self.evict_to(max_size) {
+                self.retire(key, obj);
+            }
+        }
+        self.flush_after_store()
+    }
+
+    /// Inserts a new entry with the default TTL and attaches tags to it.
+    ///
+    /// Tags group entries whose relationship isn't encoded in the key, so they
+    /// can later be found with `get_all_by_tag` or dropped together with
+    /// `invalidate_tag`. Tags are removed along with the entry when it is
+    /// evicted, expires, is removed, or is replaced by a plain `insert`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    /// * `tags` - Tags to attach to the entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached; see `insert`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_tagged("profile:42".to_string(), "Alice".to_string(), &["user:42"]);
+    /// cache.insert_tagged("feed:42".to_string(), "...".to_string(), &["user:42", "org:7"]);
+    /// cache.insert_tagged("feed:43".to_string(), "...".to_string(), &["org:7"]);
+    ///
+    /// assert_eq!(cache.invalidate_tag("user:42"), 2);
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    #[track_caller]
+    pub fn insert_tagged(&mut self, key: T, value: U, tags: &[&str])
+    where
+        T: Clone,
+    {
+        let cache_obj = SimpleCacheObject::new(value, self.max_age);
+        self.store_tagged(key, cache_obj, tags);
+    }
+
+    /// Inserts a new entry with a custom TTL and attaches tags to it.
+    ///
+    /// See `insert_tagged` for how tags behave.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    /// * `ttl` - Custom time-to-live for this specific entry
+    /// * `tags` - Tags to attach to the entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached; see `insert`.
+    #[track_caller]
+    pub fn insert_tagged_with_ttl(&mut self, key: T, value: U, ttl: Duration, tags: &[&str])
+    where
+        T: Clone,
+    {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.store_tagged(key, cache_obj, tags);
+    }
+
+    /// Removes every entry carrying the given tag.
+    ///
+    /// Entries depending on the removed entries are invalidated as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag to invalidate
+    ///
+    /// # Returns
+    ///
+    /// The number of entries that were removed, including dependents and
+    /// expired entries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_tagged(1, "first", &["batch:a"]);
+    /// cache.insert_tagged(2, "second", &["batch:a"]);
+    /// cache.insert_with_dependencies(3, "derived", &[2]);
+    /// cache.insert(4, "untagged");
+    ///
+    /// assert_eq!(cache.invalidate_tag("batch:a"), 3);
+    /// assert!(cache.contains_key(&4));
+    /// ```
+    pub fn invalidate_tag(&mut self, tag: &str) -> usize {
+        let mut removed = 0;
+        for key in self.tags.take(tag) {
+            if self.remove_entry(&key).is_some() {
+                removed += 1;
+            }
+            removed += self.invalidate_dependents(&key);
+        }
+        removed
+    }
+
+    /// Returns all active entries carrying the given tag, in insertion order.
+    ///
+    /// Expired entries carrying the tag are removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_tagged("a".to_string(), 1, &["even", "small"]);
+    /// cache.insert_tagged("b".to_string(), 2, &["small"]);
+    ///
+    /// let small = cache.get_all_by_tag("small");
+    /// assert_eq!(small.len(), 2);
+    /// ```
+    pub fn get_all_by_tag(&mut self, tag: &str) -> Vec<(&T, &SimpleCacheObject<U>)> {
+        let mut expired = Vec::new();
+        let mut live = Vec::new();
+        for (slot, _, obj) in self
+            .tags
+            .keys(tag)
+            .filter_map(|key| self.cache.get_full(key))
+        {
+            if obj.is_expired() {
+                expired.push(slot);
+            } else {
+                live.push(slot);
+            }
+        }
+        self.expire_slots(expired);
+
+        self.cache.sort_by_order(&mut live);
+        self.entries_at(live)
+    }
+
+    /// Returns the tags attached to an entry.
+    ///
+    /// Returns an empty slice if the key doesn't exist or has no tags.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_tagged("key".to_string(), "value", &["group:1"]);
+    ///
+    /// assert_eq!(cache.tags(&"key".to_string()), ["group:1"]);
+    /// ```
+    pub fn tags(&self, key: &T) -> &[String] {
+        self.tags.tags(key)
+    }
+
+    /// Inserts a new entry with the default TTL that depends on other keys.
+    ///
+    /// Whenever one of the `parents` keys is removed with `remove` or replaced
+    /// by a new insert, this entry is invalidated too, and so on transitively
+    /// for entries depending on it. Parents don't need to be cached yet.
+    /// Expiry and eviction of a parent don't cascade, but the dependency is
+    /// kept so the entry is still invalidated when the parent is re-inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to associate with the value
+    /// * `value` - The value to cache
+    /// * `parents` - Keys this entry is derived from
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry can't be cached; see `insert`. Use `try_insert`
+    /// followed by `add_dependency` to handle the error instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("profile:42".to_string(), "Alice".to_string());
+    /// cache.insert_with_dependencies(
+    ///     "page:home:42".to_string(),
+    ///     "<h1>Hi Alice</h1>".to_string(),
+    ///     &["profile:42".to_string()],
+    /// );
+    ///
+    /// // Updating the profile invalidates the rendered page
+    /// cache.insert("profile:42".to_string(), "Alicia".to_string());
+    /// assert!(!cache.contains_key(&"page:home:42".to_string()));
+    /// ```
+    #[track_caller]
+    pub fn insert_with_dependencies(&mut self, key: T, value: U, parents: &[T])
+    where
+        T: Clone,
+    {
+        let cache_obj = SimpleCacheObject::new(value, self.max_age);
+        self.store_or_panic(key.clone(), cache_obj);
+        for parent in parents {
+            self.deps.add(&key, parent);
+        }
+    }
+
+    /// Declares that an existing entry depends on another key.
+    ///
+    /// This works with entries inserted by any of the insert methods. See
+    /// `insert_with_dependencies` for how dependencies are invalidated.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the dependent entry
+    /// * `parent` - The key it depends on
+    ///
+    /// # Returns
+    ///
+    /// `true` if the dependency was recorded, `false` if `key` isn't cached
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("user:1", "Alice");
+    /// cache.insert_with_ttl("greeting:1", "Hello Alice", Duration::from_secs(60));
+    /// cache.add_dependency(&"greeting:1", &"user:1");
+    ///
+    /// cache.remove(&"user:1");
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn add_dependency(&mut self, key: &T, parent: &T) -> bool
+    where
+        T: Clone,
+    {
+        if !self.cache.contains_key(key) {
+            return false;
+        }
+        self.deps.add(key, parent);
+        true
+    }
+
+    /// Returns the keys an entry depends on.
+    ///
+    /// Returns an empty slice if the key doesn't exist or has no dependencies.
+    pub fn dependencies(&self, key: &T) -> &[T] {
+        self.deps.parents(key)
+    }
+
+    /// Records that a key doesn't exist, so lookups can skip the source of truth.
+    ///
+    /// Until `ttl` passes, `get` and `get_mut` return
+    /// `SimpleCacheError::NegativeHit` for the key without calling the loader.
+    /// Any cached value for the key is removed (invalidating its dependents),
+    /// and inserting a value for the key later drops the negative entry.
+    ///
+    /// Negative entries don't count towards `len()` and are purged by
+    /// `cleanup_expired`. They are limited separately, to the cache's size
+    /// limit unless `set_max_negative_size` says otherwise; once the limit is
+    /// reached, the oldest negative entry is dropped to make room.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key known to be missing
+    /// * `ttl` - How long to remember that the key is missing
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache: SimpleCacher<String, String> = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert_negative("user:999".to_string(), Duration::from_secs(30));
+    ///
+    /// assert_eq!(cache.get(&"user:999".to_string()).unwrap_err(), SimpleCacheError::NegativeHit);
+    /// assert!(!cache.contains_key(&"user:999".to_string()));
+    ///
+    /// // Caching a value replaces the negative entry
+    /// cache.insert("user:999".to_string(), "Zed".to_string());
+    /// assert!(cache.get(&"user:999".to_string()).is_ok());
+    /// ```
+    pub fn insert_negative(&mut self, key: T, ttl: Duration) {
+        self.invalidate_entry(&key);
+        // Recording a key again makes it the newest negative entry
+        self.negatives.remove(&key);
+
+        let limit = self.max_negative_size.or(self.max_size);
+        if limit == Some(0) {
+            return;
+        }
+        if let Some(limit) = limit {
+            self.trim_negatives(limit - 1);
+        }
+        self.negatives
+            .insert(key, SimpleCacheObject::new((), Some(ttl)));
+    }
+
+    /// Limits the number of negative entries, dropping the oldest ones above
+    /// the limit.
+    ///
+    /// By default negative entries are limited to the cache's size limit, and
+    /// unlimited if the cache is.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - Maximum number of negative entries to keep
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache: SimpleCacher<u32, String> = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.set_max_negative_size(2);
+    /// for id in 0..5 {
+    ///     cache.insert_negative(id, Duration::from_secs(30));
+    /// }
+    ///
+    /// assert_eq!(cache.negative_len(), 2);
+    /// assert!(!cache.is_negative(&0));
+    /// assert!(cache.is_negative(&4));
+    /// ```
+    pub fn set_max_negative_size(&mut self, max_size: usize) {
+        self.max_negative_size = Some(max_size);
+        self.trim_negatives(max_size);
+    }
+
+    /// Drops the oldest negative entries until at most `limit` are left.
+    fn trim_negatives(&mut self, limit: usize) {
+        while self.negatives.len() > limit {
+            self.negatives.pop_front();
+        }
+    }
+
+    /// Checks if a key is recorded as missing and the record hasn't expired.
+    pub fn is_negative<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.negatives.get(key).is_some_and(|obj| !obj.is_expired())
+    }
+
+    /// Returns the number of negative entries (including expired ones).
+    pub fn negative_len(&self) -> usize {
+        self.negatives.len()
+    }
+
+    /// Removes an entry by key and returns it if it existed.
+    ///
+    /// This method removes the entry regardless of whether it has expired.
+    /// Returns `None` if the key doesn't exist. Entries depending on the key
+    /// are invalidated as well, and a negative entry for the key is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry to remove, or any borrowed form of it
+    ///
+    /// # Returns
+    ///
+    /// `Some(SimpleCacheObject<U>)` if the key existed, `None` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("temp_data".to_string(), "temporary".to_string());
+    ///
+    /// if let Some(removed) = cache.remove("temp_data") {
+    ///     println!("Removed: {}", removed.into_value());
+    /// }
+    /// ```
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<SimpleCacheObject<U>>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.negatives.remove(key);
+        self.invalidate_entry(key)
+    }
+
+    /// Removes several entries at once.
+    ///
+    /// Behaves like calling `remove` for every key, but the entries are taken
+    /// out of the cache in a single pass instead of one removal per key.
+    /// Dependents are invalidated and negative entries dropped as with `remove`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys to remove, or any borrowed form of them
+    ///
+    /// # Returns
+    ///
+    /// The removed entries with their keys, in cache order. Keys that weren't
+    /// cached are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("a".to_string(), 1);
+    /// cache.insert("b".to_string(), 2);
+    /// cache.insert("c".to_string(), 3);
+    ///
+    /// let removed = cache.remove_many(["c", "a", "missing"]);
+    /// assert_eq!(removed.len(), 2);
+    /// assert_eq!(removed[0].0, "a");
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    pub fn remove_many<'q, Q, I>(&mut self, keys: I) -> Vec<(T, SimpleCacheObject<U>)>
+    where
+        I: IntoIterator<Item = &'q Q>,
+        Q: 'q + ?Sized + Hash + Equivalent<T>,
+    {
+        let keys: Vec<&Q> = keys.into_iter().collect();
+        let slots = keys
+            .iter()
+            .filter_map(|key| self.cache.slot_of(*key))
+            .collect();
+        let removed = self.take_slots(slots);
+
+        for key in keys {
+            self.negatives.remove(key);
+            self.invalidate_dependents(key);
+        }
+        removed
+    }
+
+    /// Checks if a key exists in the cache and is not expired.
+    ///
+    /// This is a lightweight check that doesn't trigger cleanup of expired entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to check for existence, or any borrowed form of it
+    ///
+    /// # Returns
+    ///
+    /// `true` if the key exists and is not expired, `false` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("key".to_string(), "value".to_string());
+    ///
+    /// assert!(cache.contains_key("key"));
+    /// assert!(!cache.contains_key("nonexistent"));
+    /// ```
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.cache
+            .get(key)
+            .map(|obj| !obj.is_expired())
+            .unwrap_or(false)
+    }
+
+    /// Restarts the lifetime of an entry, as if it had just been inserted.
+    ///
+    /// The entry keeps its value, TTL and metadata; only its creation time is
+    /// reset, so `age` starts over from zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry to refresh, or any borrowed form of it
+    ///
+    /// # Returns
+    ///
+    /// `true` if the entry was refreshed, `false` if it doesn't exist or has
+    /// already expired (an expired entry is removed)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut sessions = SimpleCacher::new(Duration::from_millis(50));
+    /// sessions.insert("session:1", "alice");
+    ///
+    /// std::thread::sleep(Duration::from_millis(30));
+    /// assert!(sessions.touch(&"session:1"));
+    /// std::thread::sleep(Duration::from_millis(30));
+    /// assert!(sessions.contains_key(&"session:1"));
+    /// ```
+    pub fn touch<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        match self.live_entry_mut(key) {
+            Some(obj) => {
+                obj.created_at = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the time to live of an entry, counted from now.
+    ///
+    /// Like Redis `EXPIRE`, this replaces any previous TTL, including none at
+    /// all after `persist`. The creation time and age of the entry are kept.
+    /// A zero TTL expires the entry right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry, or any borrowed form of it
+    /// * `ttl` - How long from now the entry should stay live
+    ///
+    /// # Returns
+    ///
+    /// `true` if the TTL was set, `false` if the entry doesn't exist or has
+    /// already expired
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("token", "abc123");
+    ///
+    /// assert!(cache.set_ttl(&"token", Duration::from_millis(10)));
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// assert!(!cache.contains_key(&"token"));
+    /// assert!(!cache.set_ttl(&"missing", Duration::from_secs(1)));
+    /// ```
+    pub fn set_ttl<Q>(&mut self, key: &Q, ttl: Duration) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let Some(obj) = self.live_entry_mut(key) else {
+            return false;
+        };
+        obj.max_age = Some(obj.created_at.elapsed().saturating_add(ttl));
+        if ttl.is_zero() {
+            self.expire_entry(key);
+        }
+        true
+    }
+
+    /// Makes an entry expire at the given instant.
+    ///
+    /// Like Redis `EXPIREAT`, this replaces any previous TTL, and an instant
+    /// that has already passed expires the entry right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry, or any borrowed form of it
+    /// * `when` - When the entry should expire
+    ///
+    /// # Returns
+    ///
+    /// `true` if the expiry time was set, `false` if the entry doesn't exist
+    /// or has already expired
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.insert("report", "q3 numbers");
+    ///
+    /// let end_of_window = Instant::now() + Duration::from_secs(5);
+    /// assert!(cache.expire_at(&"report", end_of_window));
+    ///
+    /// assert!(cache.expire_at(&"report", Instant::now()));
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn expire_at<Q>(&mut self, key: &Q, when: Instant) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let Some(obj) = self.live_entry_mut(key) else {
+            return false;
+        };
+        obj.max_age = Some(when.saturating_duration_since(obj.created_at));
+        if when <= Instant::now() {
+            self.expire_entry(key);
+        }
+        true
+    }
+
+    /// Removes the TTL of an entry so that it never expires.
+    ///
+    /// Like Redis `PERSIST`, the entry stays cached until it is removed,
+    /// replaced or evicted, or until a TTL is set again with `set_ttl` or
+    /// `expire_at`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the entry, or any borrowed form of it
+    ///
+    /// # Returns
+    ///
+    /// `true` if the TTL was removed, `false` if the entry doesn't exist, has
+    /// already expired or had no TTL to begin with
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_millis(10));
+    /// cache.insert("pinned", "keep me");
+    ///
+    /// assert!(cache.persist(&"pinned"));
+    /// assert!(!cache.persist(&"pinned"));
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// assert!(cache.contains_key(&"pinned"));
+    /// ```
+    pub fn persist<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        self.live_entry_mut(key)
+            .is_some_and(|obj| obj.max_age.take().is_some())
+    }
+
+    /// Manually removes all expired entries from the cache.
+    ///
+    /// Expired negative entries are purged as well but not counted.
+    ///
+    /// This method performs a full scan of the cache and removes all entries
+    /// that have exceeded their TTL. This can be useful for periodic cleanup
+    /// to free memory and maintain cache performance.
+    ///
+    /// # Returns
+    ///
+    /// The number of expired entries that were removed
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_millis(100));
+    /// cache.insert("key1".to_string(), "value1".to_string());
+    /// cache.insert("key2".to_string(), "value2".to_string());
+    ///
+    /// // Wait for expiration
+    /// std::thread::sleep(Duration::from_millis(150));
+    ///
+    /// let removed = cache.cleanup_expired();
+    /// println!("Cleaned up {} expired entries", removed);
+    /// ```
+    pub fn cleanup_expired(&mut self) -> usize {
+        // Move the expired entries out in a single pass instead of looking
+        // each one up again by key
+        let expired = self.cache.extract_if(|_, obj| obj.is_expired());
+
+        let count = expired.len();
+        for (key, obj) in expired {
+            self.forget_key(&key);
+            self.retire(key, obj);
+        }
+        self.negatives.extract_if(|_, obj| obj.is_expired());
+        count
+    }
+
+    /// Returns the total number of entries in the cache (including expired ones).
+    ///
+    /// Note that this includes expired entries that haven't been cleaned up yet.
+    /// Use `active_len()` to get only non-expired entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("key1".to_string(), "value1".to_string());
+    /// cache.insert("key2".to_string(), "value2".to_string());
+    ///
+    /// assert_eq!(cache.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns the number of non-expired entries in the cache.
+    ///
+    /// This method counts only entries that are still valid (not expired).
+    /// It does not modify the cache or remove expired entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("key1".to_string(), "value1".to_string());
+    /// cache.insert("key2".to_string(), "value2".to_string());
+    ///
+    /// assert_eq!(cache.active_len(), 2);
+    /// ```
+    pub fn active_len(&self) -> usize {
+        self.cache
+            .iter()
+            .filter(|(_, obj)| !obj.is_expired())
+            .count()
+    }
+
+    /// Returns `true` if the cache contains no entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let cache = SimpleCacher::<String, String>::new(Duration::from_secs(300));
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Removes all entries from the cache.
+    ///
+    /// After calling this method, the cache will be empty and `len()` will return 0.
+    /// Negative entries are removed too. Unflushed write-behind changes are
+    /// discarded; call `flush` first to keep them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("key".to_string(), "value".to_string());
+    /// assert_eq!(cache.len(), 1);
+    ///
+    /// cache.clear();
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        if let Some(index) = &mut self.key_index {
+            index.clear();
+        }
+        self.tags.clear();
+        self.deps.clear();
+        self.source.discard_changes();
+        self.negatives.clear();
+    }
+
+    /// Returns comprehensive statistics about the cache state.
+    ///
+    /// This provides detailed information about cache usage, including total entries,
+    /// active (non-expired) entries, expired entries, and configuration settings.
+    ///
+    /// # Returns
+    ///
+    /// A `CacheStats` struct containing cache metrics
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::with_max_size(Duration::from_secs(300), 1000);
+    /// cache.insert("key1".to_string(), "value1".to_string());
+    /// cache.insert("key2".to_string(), "value2".to_string());
+    ///
+    /// let stats = cache.stats();
+    /// println!("Active entries: {}", stats.active_entries);
+    /// println!("Max size: {:?}", stats.max_size);
+    /// ```
+    pub fn stats(&self) -> CacheStats {
+        let total = self.cache.len();
+        let (mut expired, mut persistent) = (0, 0);
+        for obj in self.cache.values() {
+            match obj.max_age {
+                None => persistent += 1,
+                Some(_) if obj.is_expired() => expired += 1,
+                Some(_) => {}
+            }
+        }
+
+        CacheStats {
+            total_entries: total,
+            active_entries: total - expired,
+            expired_entries: expired,
+            persistent_entries: persistent,
+            max_size: self.max_size,
+            max_age: self.max_age,
+        }
+    }
+
+    /// Returns when the next entry in the cache will expire.
+    ///
+    /// Lets callers schedule `cleanup_expired` for exactly when it has
+    /// something to do instead of polling. Entries that have expired but not
+    /// been removed yet are included, so the result may be in the past; a
+    /// cleanup is due right away in that case. This takes O(n) time.
+    ///
+    /// # Returns
+    ///
+    /// The earliest expiry time of any entry, or `None` if no entry ever
+    /// expires (including when the cache is empty)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// assert_eq!(cache.next_expiry(), None);
+    ///
+    /// cache.insert("long", 1);
+    /// cache.insert_with_ttl("short", 2, Duration::from_millis(10));
+    ///
+    /// let next = cache.next_expiry().unwrap();
+    /// assert_eq!(Some(next), cache.get(&"short").unwrap().expires_at());
+    ///
+    /// std::thread::sleep(next.saturating_duration_since(Instant::now()));
+    /// # std::thread::sleep(Duration::from_millis(1));
+    /// assert_eq!(cache.cleanup_expired(), 1);
+    /// ```
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.cache
+            .values()
+            .filter_map(SimpleCacheObject::expires_at)
+            .min()
+    }
+
+    /// Returns an iterator over all non-expired entries in the cache.
+    ///
+    /// This iterator yields tuples of `(&T, &SimpleCacheObject<U>)` for each
+    /// active (non-expired) entry. Expired entries are skipped.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over active cache entries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("user:1".to_string(), "Alice".to_string());
+    /// cache.insert("user:2".to_string(), "Bob".to_string());
+    ///
+    /// for (key, entry) in cache.iter_active() {
+    ///     println!("{}: {} (age: {:?})", key, entry.value(), entry.age());
+    /// }
+    /// ```
+    pub fn iter_active(&self) -> impl Iterator<Item = (&T, &SimpleCacheObject<U>)> {
+        self.cache.iter().filter(|(_, obj)| !obj.is_expired())
+    }
+
+    /// Returns `true` if the ordered key index is enabled.
+    pub fn has_key_index(&self) -> bool {
+        self.key_index.is_some()
+    }
+
+    /// Disables the ordered key index and frees its memory.
+    ///
+    /// Matcher queries fall back to scanning every entry.
+    pub fn disable_key_index(&mut self) {
+        self.key_index = None;
+    }
+
+    /// Configures a loader used to fill in entries on a cache miss.
+    ///
+    /// With a loader set, `get_or_load` and the other `_or_load` lookups load
+    /// missing and expired entries, cache them with the default TTL and return
+    /// them instead of failing with `NotFound` or `Expired`. Loader errors are
+    /// returned as `SimpleCacheError::LoadFailed`. Plain key lookups such as
+    /// `get` and matcher queries never load.
+    ///
+    /// The loader is shared with clones of the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `loader` - A `Loader` implementation or a `Fn(&T) -> Result<U, E>` closure
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.set_loader(|id: &u32| -> io::Result<String> {
+    ///     if *id < 100 {
+    ///         Ok(format!("user {}", id))
+    ///     } else {
+    ///         Err(io::Error::new(io::ErrorKind::NotFound, "no such user"))
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(cache.get_or_load(&7).unwrap().value(), "user 7");
+    /// assert!(cache.contains_key(&7));
+    /// assert!(matches!(
+    ///     cache.get_or_load(&500),
+    ///     Err(SimpleCacheError::LoadFailed(_))
+    /// ));
+    /// ```
+    pub fn set_loader<L>(&mut self, loader: L)
+    where
+        L: Loader<T, U> + Send + Sync + 'static,
+    {
+        self.source.set_loader(loader);
+    }
+
+    /// Removes the configured loader, if any.
+    pub fn clear_loader(&mut self) {
+        self.source.clear_loader();
+    }
+
+    /// Returns `true` if a loader is configured.
+    pub fn has_loader(&self) -> bool {
+        self.source.has_loader()
+    }
+
+    /// Configures a writer that inserted values are written through to.
+    ///
+    /// With a writer set, every insert method writes the value through before
+    /// caching it, and leaves the cache unchanged if the write fails. Values
+    /// filled in by the loader are not written back.
+    ///
+    /// The writer is shared with clones of the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A `Writer` implementation or a `Fn(&T, &U) -> Result<(), E>` closure
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.set_writer(|key: &String, _value: &String| -> io::Result<()> {
+    ///     if key.starts_with("readonly:") {
+    ///         return Err(io::Error::new(io::ErrorKind::PermissionDenied, "read-only"));
+    ///     }
+    ///     Ok(())
+    /// });
+    ///
+    /// cache.try_insert("user:1".to_string(), "Alice".to_string()).unwrap();
+    /// assert!(cache
+    ///     .try_insert("readonly:1".to_string(), "Bob".to_string())
+    ///     .is_err());
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    pub fn set_writer<W>(&mut self, writer: W)
+    where
+        W: Writer<T, U> + Send + Sync + 'static,
+    {
+        self.source.set_writer(writer);
+    }
+
+    /// Removes the configured writer, if any.
+    ///
+    /// Unflushed changes are kept and written once a writer is configured again.
+    pub fn clear_writer(&mut self) {
+        self.source.clear_writer();
+    }
+
+    /// Chooses between writing inserts through immediately and batching them.
+    ///
+    /// In `WriteMode::Behind`, inserts and `get_mut` only mark entries dirty
+    /// and `flush` writes them in one `Writer::write_batch` call. Dirty
+    /// entries that are evicted or expire before being flushed are not lost:
+    /// their last value is written by the next insert or flush. While the
+    /// writer keeps failing, at most one such value is kept per key. Entries
+    /// that are explicitly removed, invalidated or cleared are dropped along
+    /// with their unwritten changes.
+    ///
+    /// Switching back to `WriteMode::Through` doesn't flush; call `flush`
+    /// first to write outstanding changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The new write mode; the default is `WriteMode::Through`
+    pub fn set_write_mode(&mut self, mode: WriteMode) {
+        self.source.set_mode(mode);
+    }
+
+    /// Returns the current write mode.
+    pub fn write_mode(&self) -> WriteMode {
+        self.source.mode()
+    }
+
+    /// Writes all dirty entries to the configured writer in one batch.
+    ///
+    /// Entries are written in cache order, after any dirty entries that were
+    /// evicted or expired before the flush. If the writer fails, every entry
+    /// stays dirty so the next flush retries it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of entries written
+    /// * `Err(SimpleCacheError::WriteFailed)` - The writer failed
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(60));
+    /// cache.set_writer(|_: &&str, _: &u32| -> io::Result<()> { Ok(()) });
+    /// cache.set_write_mode(WriteMode::Behind { flush_interval: None });
+    ///
+    /// cache.insert("a", 1);
+    /// cache.insert("b", 2);
+    /// cache.insert("a", 3);
+    /// assert_eq!(cache.dirty_len(), 2);
+    ///
+    /// assert_eq!(cache.flush().unwrap(), 2);
+    /// assert_eq!(cache.dirty_len(), 0);
+    /// ```
+    pub fn flush(&mut self) -> Result<usize, SimpleCacheError> {
+        if !self.source.has_writer() {
+            return Ok(0);
+        }
+        let source = &self.source;
+        let batch: Vec<(&T, &U)> = source
+            .pending()
+            .chain(
+                self.cache
+                    .iter()
+                    .filter(|(_, obj)| obj.dirty)
+                    .map(|(key, obj)| (key, &obj.value)),
+            )
+            .collect();
+        let written = batch.len();
+
+        source
+            .write_batch(&batch)
+            .map_err(|err| SimpleCacheError::WriteFailed(Arc::from(err)))?;
+        self.source.mark_flushed();
+        for obj in self.cache.values_mut() {
+            obj.dirty = false;
+        }
+        Ok(written)
+    }
+
+    /// Returns the number of entries with changes that haven't been written yet.
+    ///
+    /// This scans the cache, so it takes O(n) time.
+    pub fn dirty_len(&self) -> usize {
+        let cached = self.cache.values().filter(|obj| obj.dirty).count();
+        cached + self.source.pending_len()
+    }
+
+    /// Caches an entry and writes it through, or marks it dirty in write-behind mode.
+    ///
+    /// Returns the value of the live entry it replaced, if any.
+    fn store(
+        &mut self,
+        key: T,
+        cache_obj: SimpleCacheObject<U>,
+    ) -> Result<Option<U>, SimpleCacheError> {
+        let replaced = self.cache_entry(key, cache_obj)?;
+        self.flush_after_store()?;
+        Ok(replaced)
+    }
+
+    /// Writes an entry through (or marks it dirty) and caches it, retiring
+    /// the evicted entries.
+    ///
+    /// Returns the value of the live entry it replaced, if any.
+    fn cache_entry(
+        &mut self,
+        key: T,
+        cache_obj: SimpleCacheObject<U>,
+    ) -> Result<Option<U>, SimpleCacheError> {
+        let (replaced, evicted) = self.store_evicting(key, cache_obj)?;
+        for (key, obj) in evicted {
+            self.retire(key, obj);
+        }
+        Ok(replaced.map(SimpleCacheObject::into_value))
+    }
+
+    /// Like `store`, but hands the evicted entries to the caller, writing the
+    /// dirty ones first.
+    ///
+    /// If that write fails, the dirty entries are retired and the clean ones
+    /// dropped.
+    fn store_returning_evicted(
+        &mut self,
+        key: T,
+        cache_obj: SimpleCacheObject<U>,
+    ) -> Result<Evicted<T, U>, SimpleCacheError> {
+        let (_, mut evicted) = self.store_evicting(key, cache_obj)?;
+
+        let dirty: Vec<(&T, &U)> = evicted
+            .iter()
+            .filter(|(_, obj)| obj.dirty)
+            .map(|(key, obj)| (key, &obj.value))
+            .collect();
+        if let Err(err) = self.source.write_batch(&dirty) {
+            for (key, obj) in evicted.into_iter().filter(|(_, obj)| obj.dirty) {
+                self.retire(key, obj);
+            }
+            return Err(SimpleCacheError::WriteFailed(Arc::from(err)));
+        }
+        for (_, obj) in &mut evicted {
+            obj.dirty = false;
+        }
+
+        self.flush_after_store()?;
+        Ok(evicted)
+    }
+
+    /// Stores an entry with `store_or_panic` and attaches tags to it.
+    #[track_caller]
+    fn store_tagged(&mut self, key: T, cache_obj: SimpleCacheObject<U>, tags: &[&str])
+    where
+        T: Clone,
+    {
+        self.store_or_panic(key.clone(), cache_obj);
+        self.tags.set(&key, tags);
+    }
+
+    /// Stores an entry for the insert methods that don't return errors.
+    ///
+    /// Panics if the entry can't be cached. A failed flush afterwards keeps
+    /// the dirty entries queued for the next flush, so it isn't fatal.
+    #[track_caller]
+    fn store_or_panic(&mut self, key: T, cache_obj: SimpleCacheObject<U>) -> Option<U> {
+        let replaced = match self.cache_entry(key, cache_obj) {
+            Ok(replaced) => replaced,
+            Err(err) => panic!("failed to cache entry: {}", err),
+        };
+        // Dirty entries that fail to flush stay queued, and `flush` reports the error
+        let _ = self.flush_after_store();
+        replaced
+    }
+
+    /// Writes an entry through (or marks it dirty) and caches it, returning
+    /// the replaced entry and the evicted entries without retiring them.
+    fn store_evicting(
+        &mut self,
+        key: T,
+        mut cache_obj: SimpleCacheObject<U>,
+    ) -> Result<Stored<T, U>, SimpleCacheError> {
+        self.check_capacity()?;
+        if self.source.is_write_behind() {
+            cache_obj.dirty = true;
+        } else {
+            self.source
+                .write(&key, &cache_obj.value)
+                .map_err(|err| SimpleCacheError::WriteFailed(Arc::from(err)))?;
+        }
+        Ok(self.insert_object_evicting(key, cache_obj))
+    }
+
+    /// Flushes everything if the flush interval has passed, and otherwise
+    /// only the dirty entries that have left the cache.
+    fn flush_after_store(&mut self) -> Result<(), SimpleCacheError> {
+        if self.source.flush_due() {
+            self.flush()?;
+        } else {
+            self.flush_pending()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty entries that have already left the cache.
+    fn flush_pending(&mut self) -> Result<(), SimpleCacheError> {
+        if !self.source.has_writer() {
+            return Ok(());
+        }
+        let batch: Vec<(&T, &U)> = self.source.pending().collect();
+        self.source
+            .write_batch(&batch)
+            .map_err(|err| SimpleCacheError::WriteFailed(Arc::from(err)))?;
+        self.source.clear_pending();
+        Ok(())
+    }
+
+    /// Fails with `CapacityRejected` if the cache can't hold any entries.
+    fn check_capacity(&self) -> Result<(), SimpleCacheError> {
+        match self.max_size {
+            Some(0) => Err(SimpleCacheError::CapacityRejected { max_size: 0 }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes sure `key` has a live entry, removing it if it has expired.
+    ///
+    /// Misses are passed to `load`, which either caches an entry for the key
+    /// or returns the error to report.
+    fn ensure_live<Q, F>(&mut self, key: &Q, mut load: F) -> Result<(), SimpleCacheError>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+        F: FnMut(&mut Self, SimpleCacheError) -> Result<(), SimpleCacheError>,
+    {
+        let expired_for = match self.cache.get(key) {
+            Some(obj) if obj.is_expired() => obj.expired_for(),
+            Some(_) => return Ok(()),
+            None => {
+                self.check_negative(key)?;
+                return load(self, SimpleCacheError::NotFound);
+            }
+        };
+
+        self.expire_entry(key);
+        load(self, SimpleCacheError::Expired { expired_for })
+    }
+
+    /// Hands out a live entry for modification, marking it dirty in
+    /// `WriteMode::Behind`.
+    fn modify_live<Q>(&mut self, key: &Q) -> &mut SimpleCacheObject<U>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let write_behind = self.source.is_write_behind();
+        let obj = self.cache.get_mut(key).unwrap();
+
+        // The caller may change the value, so it has to be written back
+        obj.dirty |= write_behind;
+        obj.record_access();
+        obj
+    }
+
+    /// Shared implementation of `get_or_take_expired` and
+    /// `get_or_load_or_take_expired`; misses are passed to `load` as in
+    /// `ensure_live`.
+    fn take_expired_with<Q, F>(
+        &mut self,
+        key: &Q,
+        mut load: F,
+    ) -> Result<MaybeStale<'_, U>, SimpleCacheError>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+        F: FnMut(&mut Self, SimpleCacheError) -> Result<(), SimpleCacheError>,
+    {
+        let expired = match self.cache.get(key) {
+            Some(obj) if obj.is_expired() => self.cache.remove(key),
+            Some(_) => None,
+            None => {
+                self.check_negative(key)?;
+                load(self, SimpleCacheError::NotFound)?;
+                None
+            }
+        };
+
+        if let Some((stale_key, mut stale)) = expired {
+            self.forget_key(&stale_key);
+            let miss = SimpleCacheError::Expired {
+                expired_for: stale.expired_for(),
+            };
+            if load(self, miss).is_ok() {
+                self.retire(stale_key, stale);
+            } else {
+                // The caller takes the value, so its changes are written now
+                if stale.dirty {
+                    if let Err(err) = self.source.write_batch(&[(&stale_key, &stale.value)]) {
+                        self.retire(stale_key, stale);
+                        return Err(SimpleCacheError::WriteFailed(Arc::from(err)));
+                    }
+                    stale.dirty = false;
+                }
+                stale.record_access();
+                return Ok(MaybeStale::Stale(stale));
+            }
+        }
+
+        let obj = self.cache.get_mut(key).unwrap();
+        obj.record_access();
+        Ok(MaybeStale::Fresh(obj))
+    }
+
+    /// Shared implementation of `get_many` and `get_many_or_load`; misses are
+    /// passed to `load` together with their key.
+    fn get_many_with<'q, Q, I, F>(
+        &mut self,
+        keys: I,
+        mut load: F,
+    ) -> Vec<Result<&SimpleCacheObject<U>, SimpleCacheError>>
+    where
+        I: IntoIterator<Item = &'q Q>,
+        Q: 'q + ?Sized + Hash + Equivalent<T>,
+        F: FnMut(&mut Self, &Q, SimpleCacheError) -> Result<(), SimpleCacheError>,
+    {
+        let keys: Vec<&Q> = keys.into_iter().collect();
+
+        let mut expired = Vec::new();
+        let mut misses: Vec<Option<SimpleCacheError>> = keys
             .iter()
-            .filter_map(|(k, v)| {
-                if v.is_expired() {
-                    Some(k.clone())
-                } else {
-                    None
+            .map(|key| match self.cache.get_full(*key) {
+                Some((i, _, obj)) if obj.is_expired() => {
+                    expired.push(i);
+                    Some(SimpleCacheError::Expired {
+                        expired_for: obj.expired_for(),
+                    })
                 }
+                Some(_) => None,
+                None => Some(SimpleCacheError::NotFound),
             })
             .collect();
+        self.expire_slots(expired);
 
-        let count = expired_keys.len();
-        for key in expired_keys {
-            self.cache.shift_remove(&key);
+        for (key, miss) in keys.iter().zip(&mut misses) {
+            let Some(err) = miss.take() else {
+                continue;
+            };
+            // Duplicate keys may already have been loaded
+            if self.cache.contains_key(*key) {
+                continue;
+            }
+            let result = if err == SimpleCacheError::NotFound {
+                self.check_negative(*key)
+                    .and_then(|()| load(self, *key, err))
+            } else {
+                load(self, *key, err)
+            };
+            *miss = result.err();
         }
-        count
+
+        for (key, miss) in keys.iter().zip(&misses) {
+            if let (None, Some(obj)) = (miss, self.cache.get_mut(*key)) {
+                obj.record_access();
+            }
+        }
+
+        keys.into_iter()
+            .zip(misses)
+            .map(|(key, miss)| match miss {
+                Some(err) => Err(err),
+                None => self.cache.get(key).ok_or(SimpleCacheError::NotFound),
+            })
+            .collect()
     }
 
-    /// Returns the total number of entries in the cache (including expired ones).
-    ///
-    /// Note that this includes expired entries that haven't been cleaned up yet.
-    /// Use `active_len()` to get only non-expired entries.
-    ///
-    /// # Examples
+    /// Fails with `NegativeHit` if `key` has a live negative entry, dropping
+    /// the entry if it has expired.
+    fn check_negative<Q>(&mut self, key: &Q) -> Result<(), SimpleCacheError>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        match self.negatives.get(key) {
+            Some(obj) if obj.is_expired() => {
+                self.negatives.remove(key);
+                Ok(())
+            }
+            Some(_) => Err(SimpleCacheError::NegativeHit),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads a missing entry with the configured loader and caches it.
     ///
-    /// ```rust
-    /// use simple_cacher::*;
-    /// use std::time::Duration;
+    /// Returns `miss` if no loader is configured.
+    fn load_missing(&mut self, key: &T, miss: SimpleCacheError) -> Result<(), SimpleCacheError>
+    where
+        T: Clone,
+    {
+        if !self.source.has_loader() {
+            return Err(miss);
+        }
+        self.check_capacity()?;
+
+        match self.source.load(key) {
+            Some(Ok(value)) => {
+                let cache_obj =
+                    SimpleCacheObject::with_source(value, self.max_age, EntrySource::Loaded);
+                self.insert_object(key.clone(), cache_obj);
+                Ok(())
+            }
+            Some(Err(err)) => Err(SimpleCacheError::LoadFailed(Arc::from(err))),
+            None => Err(miss),
+        }
+    }
+
+    /// Stores an entry, evicting the oldest entries first if the cache is full.
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("key1".to_string(), "value1".to_string());
-    /// cache.insert("key2".to_string(), "value2".to_string());
+    /// Tags and dependencies of a replaced entry are dropped, and entries
+    /// depending on the key are invalidated.
     ///
-    /// assert_eq!(cache.len(), 2);
-    /// ```
-    pub fn len(&self) -> usize {
-        self.cache.len()
+    /// Evicted entries with unwritten changes are kept for the next flush.
+    /// Returns the replaced entry unless it had expired.
+    fn insert_object(
+        &mut self,
+        key: T,
+        cache_obj: SimpleCacheObject<U>,
+    ) -> Option<SimpleCacheObject<U>> {
+        let (replaced, evicted) = self.insert_object_evicting(key, cache_obj);
+        for (key, obj) in evicted {
+            self.retire(key, obj);
+        }
+        replaced
     }
 
-    /// Returns the number of non-expired entries in the cache.
+    /// Like `insert_object`, but returns the entries evicted to make room
+    /// instead of retiring them.
     ///
-    /// This method counts only entries that are still valid (not expired).
-    /// It does not modify the cache or remove expired entries.
+    /// Replacing an existing key keeps its position and never evicts.
+    fn insert_object_evicting(&mut self, key: T, cache_obj: SimpleCacheObject<U>) -> Stored<T, U> {
+        self.invalidate_dependents(&key);
+
+        // Enforce max size by removing oldest entries (FIFO)
+        let evicted = match self.max_size {
+            Some(max_size) if !self.cache.contains_key(&key) => {
+                self.evict_to(max_size.saturating_sub(1))
+            }
+            _ => Vec::new(),
+        };
+
+        let replaced = self.place_object(key, cache_obj);
+        (replaced, evicted)
+    }
+
+    /// Stores an entry without enforcing the size limit, returning the
+    /// replaced entry unless it had expired.
     ///
-    /// # Examples
+    /// The caller is responsible for invalidating dependents of `key` first.
+    fn place_object(
+        &mut self,
+        key: T,
+        cache_obj: SimpleCacheObject<U>,
+    ) -> Option<SimpleCacheObject<U>> {
+        if let Some(index) = &mut self.key_index {
+            index.insert(&key);
+        }
+        self.tags.remove_key(&key);
+        self.deps.remove_child(&key);
+        self.negatives.remove(&key);
+        self.cache
+            .insert(key, cache_obj)
+            .filter(|obj| !obj.is_expired())
+    }
+
+    /// Removes the oldest entries until at most `limit` remain, and returns
+    /// them without retiring them.
+    fn evict_to(&mut self, limit: usize) -> Evicted<T, U> {
+        let excess = self.cache.len().saturating_sub(limit);
+        let mut evicted = Vec::with_capacity(excess);
+        for _ in 0..excess {
+            let Some((key, obj)) = self.cache.pop_front() else {
+                break;
+            };
+            self.forget_key(&key);
+            evicted.push((key, obj));
+        }
+        evicted
+    }
+
+    /// Removes an entry and keeps the secondary indexes in sync.
     ///
-    /// ```rust
-    /// use simple_cacher::*;
-    /// use std::time::Duration;
+    /// Unwritten changes to the entry are discarded.
+    fn remove_entry<Q>(&mut self, key: &Q) -> Option<SimpleCacheObject<U>>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let (key, obj) = self.cache.remove(key)?;
+        self.forget_key(&key);
+        Some(obj)
+    }
+
+    /// Returns a live entry for updating its lifetime, removing it first if
+    /// it has expired.
+    fn live_entry_mut<Q>(&mut self, key: &Q) -> Option<&mut SimpleCacheObject<U>>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        if self.cache.get(key)?.is_expired() {
+            self.expire_entry(key);
+            return None;
+        }
+        self.cache.get_mut(key)
+    }
+
+    /// Removes an expired entry, keeping unwritten changes for the next flush.
+    fn expire_entry<Q>(&mut self, key: &Q)
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        if let Some((key, obj)) = self.cache.remove(key) {
+            self.forget_key(&key);
+            self.retire(key, obj);
+        }
+    }
+
+    /// Removes the expired entries in the given slots, keeping unwritten
+    /// changes for the next flush.
+    fn expire_slots(&mut self, slots: Vec<usize>) {
+        for (key, obj) in self.take_slots(slots) {
+            self.retire(key, obj);
+        }
+    }
+
+    /// Removes the entries in the given slots and returns them in cache
+    /// order, dropping them from the secondary indexes.
+    ///
+    /// Slots may be in any order and contain duplicates. Unwritten changes
+    /// are left to the caller.
+    fn take_slots(&mut self, mut slots: Vec<usize>) -> Vec<(T, SimpleCacheObject<U>)> {
+        self.cache.sort_by_order(&mut slots);
+        slots.dedup();
+
+        let taken: Vec<(T, SimpleCacheObject<U>)> = slots
+            .into_iter()
+            .filter_map(|slot| self.cache.remove_slot(slot))
+            .collect();
+        for (key, _) in &taken {
+            self.forget_key(key);
+        }
+        taken
+    }
+
+    /// Keeps an entry leaving the cache for the next flush if it has
+    /// unwritten changes.
+    fn retire(&mut self, key: T, obj: SimpleCacheObject<U>) {
+        if obj.dirty {
+            self.source.retire(key, obj.value);
+        }
+    }
+
+    /// Removes an entry and cascades the removal to its dependents.
+    fn invalidate_entry<Q>(&mut self, key: &Q) -> Option<SimpleCacheObject<U>>
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let removed = self.remove_entry(key);
+        self.invalidate_dependents(key);
+        removed
+    }
+
+    /// Removes every entry that transitively depends on `key`, and returns
+    /// how many were cached.
+    fn invalidate_dependents<Q>(&mut self, key: &Q) -> usize
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+    {
+        let mut removed = 0;
+        let mut pending: Vec<T> = self.deps.take_dependents(key).into_iter().collect();
+        while let Some(child) = pending.pop() {
+            if self.remove_entry(&child).is_some() {
+                removed += 1;
+            }
+            pending.extend(self.deps.take_dependents(&child));
+        }
+        removed
+    }
+
+    /// Drops a removed key from the key index, tag index and dependency graph.
     ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("key1".to_string(), "value1".to_string());
-    /// cache.insert("key2".to_string(), "value2".to_string());
+    /// Entries depending on `key` are left in place.
+    fn forget_key(&mut self, key: &T) {
+        if let Some(index) = &mut self.key_index {
+            index.remove(key);
+        }
+        self.tags.remove_key(key);
+        self.deps.remove_child(key);
+    }
+
+    /// Returns the slots of all active entries matching `matcher`.
     ///
-    /// assert_eq!(cache.active_len(), 2);
-    /// ```
-    pub fn active_len(&self) -> usize {
+    /// Expired entries are removed first, so the slots stay valid until
+    /// entries are removed again. Slots are in key order when the key index is
+    /// used and in insertion order otherwise.
+    fn matching_slots<M>(&mut self, matcher: &M) -> Vec<usize>
+    where
+        M: Matcher<T>,
+    {
+        if let Some((lower, upper)) = matcher.key_range() {
+            if self.key_index.is_some() {
+                return self.indexed_slots(lower, upper, |key| matcher.matches(key));
+            }
+        }
+
+        // Clean up expired entries first
+        self.cleanup_expired();
+
         self.cache
-            .iter()
-            .filter(|(_, obj)| !obj.is_expired())
-            .count()
+            .iter_full()
+            .filter(|(_, key, obj)| !obj.is_expired() && matcher.matches(key))
+            .map(|(slot, _, _)| slot)
+            .collect()
     }
 
-    /// Returns `true` if the cache contains no entries.
-    ///
-    /// # Examples
+    /// Resolves entry slots into key/entry references.
+    fn entries_at(&self, slots: Vec<usize>) -> Vec<(&T, &SimpleCacheObject<U>)> {
+        slots
+            .into_iter()
+            .filter_map(|slot| self.cache.get_slot(slot))
+            .collect()
+    }
+
+    /// Returns the slots of the active entries within the given bounds that
+    /// satisfy `filter`, in key order, using the key index.
     ///
-    /// ```rust
-    /// use simple_cacher::*;
-    /// use std::time::Duration;
+    /// Expired entries within the bounds are removed. Must only be called when
+    /// the key index is enabled.
+    fn indexed_slots<F>(&mut self, lower: Bound<&T>, upper: Bound<&T>, filter: F) -> Vec<usize>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let index = self.key_index.as_ref().expect("key index is enabled");
+        let mut expired = Vec::new();
+        let mut live = Vec::new();
+        for key in index.range(lower, upper).filter(|key| filter(key)) {
+            match self.cache.get_full(key) {
+                Some((slot, _, obj)) if obj.is_expired() => expired.push(slot),
+                Some((slot, _, _)) => live.push(slot),
+                None => {}
+            }
+        }
+        self.expire_slots(expired);
+        live
+    }
+
+    /// Looks up the slot of the first match for `matcher` through the key
+    /// index, removing expired matches that come before it in key order.
     ///
-    /// let cache = SimpleCacher::<String, String>::new(Duration::from_secs(300));
-    /// assert!(cache.is_empty());
-    /// ```
-    pub fn is_empty(&self) -> bool {
-        self.cache.is_empty()
+    /// Returns `None` if the index or the matcher's range hint is unavailable,
+    /// in which case the caller falls back to a full scan.
+    fn find_indexed<M>(&mut self, matcher: &M) -> Option<Option<usize>>
+    where
+        M: Matcher<T>,
+    {
+        let (lower, upper) = matcher.key_range()?;
+        let index = self.key_index.as_ref()?;
+
+        let mut expired = Vec::new();
+        let mut found = None;
+
+        for key in index.range(lower, upper) {
+            if !matcher.matches(key) {
+                continue;
+            }
+            match self.cache.get_full(key) {
+                Some((slot, _, obj)) if obj.is_expired() => expired.push(slot),
+                Some((slot, _, _)) => {
+                    found = Some(slot);
+                    break;
+                }
+                None => {}
+            }
+        }
+
+        self.expire_slots(expired);
+        Some(found)
     }
+}
 
-    /// Removes all entries from the cache.
+impl<T, U> SimpleCacher<T, U>
+where
+    T: Clone + Ord + std::hash::Hash + 'static,
+{
+    /// Enables an ordered index over the cache keys.
     ///
-    /// After calling this method, the cache will be empty and `len()` will return 0.
+    /// With the index enabled, `get_by_matcher` and `get_all_by_matcher` only
+    /// visit the keys within the matcher's `key_range` hint, so queries with
+    /// `PrefixMatcher` and `RangeMatcher` take O(log n + k) time instead of
+    /// scanning the whole cache. It also speeds up `range`. The index costs one
+    /// extra copy of every key and a O(log n) update on each insert and removal.
+    /// Enabling an already enabled index is a no-op.
     ///
     /// # Examples
     ///
@@ -836,24 +3544,35 @@ where
     /// use std::time::Duration;
     ///
     /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("key".to_string(), "value".to_string());
-    /// assert_eq!(cache.len(), 1);
+    /// cache.enable_key_index();
     ///
-    /// cache.clear();
-    /// assert_eq!(cache.len(), 0);
+    /// cache.insert("user:alice".to_string(), "Alice".to_string());
+    /// cache.insert("user:bob".to_string(), "Bob".to_string());
+    /// cache.insert("admin:charlie".to_string(), "Charlie".to_string());
+    ///
+    /// let users = cache.get_all_by_matcher(&PrefixMatcher::new("user:"));
+    /// assert_eq!(users.len(), 2);
+    /// assert_eq!(users[0].0, "user:alice");
     /// ```
-    pub fn clear(&mut self) {
-        self.cache.clear();
+    pub fn enable_key_index(&mut self)
+    where
+        T: Send + Sync,
+    {
+        if self.key_index.is_none() {
+            self.key_index = Some(Box::new(BTreeKeyIndex::from_keys(self.cache.keys())));
+        }
     }
 
-    /// Returns comprehensive statistics about the cache state.
+    /// Returns all active entries whose keys fall within `range`, in key order.
     ///
-    /// This provides detailed information about cache usage, including total entries,
-    /// active (non-expired) entries, expired entries, and configuration settings.
+    /// With the key index enabled this takes O(log n + k) time, where k is the
+    /// number of entries in the range. Without it, every entry is scanned and
+    /// the matches are sorted. Expired entries within the range are removed.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A `CacheStats` struct containing cache metrics
+    /// * `range` - Any range of keys, such as `10..20`, `..=5` or
+    ///   `(Bound::Excluded(a), Bound::Unbounded)`
     ///
     /// # Examples
     ///
@@ -861,56 +3580,104 @@ where
     /// use simple_cacher::*;
     /// use std::time::Duration;
     ///
-    /// let mut cache = SimpleCacher::with_max_size(Duration::from_secs(300), 1000);
-    /// cache.insert("key1".to_string(), "value1".to_string());
-    /// cache.insert("key2".to_string(), "value2".to_string());
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.enable_key_index();
     ///
-    /// let stats = cache.stats();
-    /// println!("Active entries: {}", stats.active_entries);
-    /// println!("Max size: {:?}", stats.max_size);
+    /// for id in [42, 7, 19, 3, 25] {
+    ///     cache.insert(id, format!("order #{}", id));
+    /// }
+    ///
+    /// let keys: Vec<i32> = cache.range(5..25).into_iter().map(|(k, _)| *k).collect();
+    /// assert_eq!(keys, vec![7, 19]);
     /// ```
-    pub fn stats(&self) -> CacheStats {
-        let total = self.cache.len();
+    pub fn range<R>(&mut self, range: R) -> Vec<(&T, &SimpleCacheObject<U>)>
+    where
+        R: RangeBounds<T>,
+    {
+        let (lower, upper) = (range.start_bound(), range.end_bound());
+
+        if self.key_index.is_some() {
+            let slots = self.indexed_slots(lower, upper, |_| true);
+            return self.entries_at(slots);
+        }
+
         let expired = self
+            .cache
+            .iter_full()
+            .filter(|(_, key, obj)| obj.is_expired() && range.contains(key))
+            .map(|(slot, _, _)| slot)
+            .collect();
+        self.expire_slots(expired);
+
+        let mut entries: Vec<_> = self
             .cache
             .iter()
-            .filter(|(_, obj)| obj.is_expired())
-            .count();
+            .filter(|(key, _)| range.contains(key))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
 
-        CacheStats {
-            total_entries: total,
-            active_entries: total - expired,
-            expired_entries: expired,
-            max_size: self.max_size,
-            max_age: self.max_age,
+/// Result of `SimpleCacher::get_or_take_expired`.
+#[derive(Debug)]
+pub enum MaybeStale<'a, U> {
+    /// A live entry, still in the cache.
+    Fresh(&'a SimpleCacheObject<U>),
+    /// An entry that had expired and was taken out of the cache.
+    Stale(SimpleCacheObject<U>),
+}
+
+impl<U> MaybeStale<'_, U> {
+    /// Returns the entry, fresh or stale.
+    pub fn entry(&self) -> &SimpleCacheObject<U> {
+        match self {
+            MaybeStale::Fresh(entry) => entry,
+            MaybeStale::Stale(entry) => entry,
         }
     }
 
-    /// Returns an iterator over all non-expired entries in the cache.
-    ///
-    /// This iterator yields tuples of `(&T, &SimpleCacheObject<U>)` for each
-    /// active (non-expired) entry. Expired entries are skipped.
-    ///
-    /// # Returns
-    ///
-    /// An iterator over active cache entries
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use simple_cacher::*;
-    /// use std::time::Duration;
-    ///
-    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
-    /// cache.insert("user:1".to_string(), "Alice".to_string());
-    /// cache.insert("user:2".to_string(), "Bob".to_string());
+    /// Returns the cached value, fresh or stale.
+    pub fn value(&self) -> &U {
+        self.entry().value()
+    }
+
+    /// Returns `true` if the entry had expired.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, MaybeStale::Stale(_))
+    }
+}
+
+/// Ordering used to pick and sort entries returned by matcher queries.
+///
+/// See `SimpleCacher::get_by_matcher_ordered` and
+/// `SimpleCacher::get_all_by_matcher_sorted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOrder {
+    /// Order in which keys were first inserted into the cache.
+    Insertion,
+    /// Least recently created or replaced entries first.
+    Oldest,
+    /// Most recently created or replaced entries first.
+    Newest,
+    /// Entries with the most time left before expiring first.
+    LongestTtl,
+    /// Entries closest to expiring first.
+    ShortestTtl,
+}
+
+impl MatchOrder {
+    /// Compares two entries according to this ordering.
     ///
-    /// for (key, entry) in cache.iter_active() {
-    ///     println!("{}: {} (age: {:?})", key, entry.value(), entry.age());
-    /// }
-    /// ```
-    pub fn iter_active(&self) -> impl Iterator<Item = (&T, &SimpleCacheObject<U>)> {
-        self.cache.iter().filter(|(_, obj)| !obj.is_expired())
+    /// `Insertion` can't be decided from the entries alone and compares equal.
+    fn compare<U>(&self, a: &SimpleCacheObject<U>, b: &SimpleCacheObject<U>) -> std::cmp::Ordering {
+        match self {
+            MatchOrder::Insertion => std::cmp::Ordering::Equal,
+            MatchOrder::Oldest => a.created_at.cmp(&b.created_at),
+            MatchOrder::Newest => b.created_at.cmp(&a.created_at),
+            MatchOrder::LongestTtl => b.time_left().cmp(&a.time_left()),
+            MatchOrder::ShortestTtl => a.time_left().cmp(&b.time_left()),
+        }
     }
 }
 
@@ -926,10 +3693,12 @@ pub struct CacheStats {
     pub active_entries: usize,
     /// Number of expired entries (not yet cleaned up)
     pub expired_entries: usize,
+    /// Number of entries that never expire (included in `active_entries`)
+    pub persistent_entries: usize,
     /// Maximum number of entries allowed (None if unlimited)
     pub max_size: Option<usize>,
-    /// Default time-to-live for new entries
-    pub max_age: Duration,
+    /// Default time-to-live for new entries (None if they never expire)
+    pub max_age: Option<Duration>,
 }
 
 // ========== Built-in Matchers ==========
@@ -975,6 +3744,10 @@ where
     fn matches(&self, key: &T) -> bool {
         key == &self.target
     }
+
+    fn key_range(&self) -> Option<(Bound<&T>, Bound<&T>)> {
+        Some((Bound::Included(&self.target), Bound::Included(&self.target)))
+    }
 }
 
 /// String prefix matcher for finding keys that start with a specific string.
@@ -999,6 +3772,7 @@ where
 /// ```
 pub struct PrefixMatcher {
     prefix: String,
+    upper: Option<String>,
 }
 
 impl PrefixMatcher {
@@ -1008,9 +3782,9 @@ impl PrefixMatcher {
     ///
     /// * `prefix` - The prefix string to match against
     pub fn new(prefix: impl Into<String>) -> Self {
-        Self {
-            prefix: prefix.into(),
-        }
+        let prefix = prefix.into();
+        let upper = index::prefix_upper_bound(&prefix);
+        Self { prefix, upper }
     }
 }
 
@@ -1018,6 +3792,14 @@ impl Matcher<String> for PrefixMatcher {
     fn matches(&self, key: &String) -> bool {
         key.starts_with(&self.prefix)
     }
+
+    fn key_range(&self) -> Option<(Bound<&String>, Bound<&String>)> {
+        let upper = match &self.upper {
+            Some(upper) => Bound::Excluded(upper),
+            None => Bound::Unbounded,
+        };
+        Some((Bound::Included(&self.prefix), upper))
+    }
 }
 
 impl Matcher<&str> for PrefixMatcher {
@@ -1145,6 +3927,24 @@ impl Matcher<&str> for ContainsMatcher {
 /// let high_scores = cache.get_all_by_matcher(&high_score_matcher);
 /// assert_eq!(high_scores.len(), 2); // 85 and 92
 /// ```
+///
+/// With the key index enabled, range matchers only visit keys inside the range
+/// and return them in ascending key order:
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::time::Duration;
+///
+/// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+/// cache.enable_key_index();
+/// cache.insert(92, "Excellent score".to_string());
+/// cache.insert(85, "Good score".to_string());
+/// cache.insert(67, "Average score".to_string());
+///
+/// let high_scores = cache.get_all_by_matcher(&RangeMatcher::new(80, 100));
+/// assert_eq!(*high_scores[0].0, 85);
+/// assert_eq!(*high_scores[1].0, 92);
+/// ```
 pub struct RangeMatcher<T> {
     min: T,
     max: T,
@@ -1192,6 +3992,14 @@ where
             key > &self.min && key < &self.max
         }
     }
+
+    fn key_range(&self) -> Option<(Bound<&T>, Bound<&T>)> {
+        if self.inclusive {
+            Some((Bound::Included(&self.min), Bound::Included(&self.max)))
+        } else {
+            Some((Bound::Excluded(&self.min), Bound::Excluded(&self.max)))
+        }
+    }
 }
 
 /// Function-based matcher for maximum flexibility in matching logic.
@@ -1292,7 +4100,7 @@ mod tests {
         thread::sleep(Duration::from_millis(150));
         assert!(matches!(
             cache.get(&"key1".to_string()),
-            Err(SimpleCacheError::Expired)
+            Err(SimpleCacheError::Expired { .. })
         ));
     }
 
@@ -1336,9 +4144,58 @@ mod tests {
         let result = cache.get_by_matcher(&matcher);
         assert!(result.is_ok());
 
-        // Should find either key 5 or 10
-        let found_value = result.unwrap().value();
-        assert!(found_value.to_string() == "value5" || found_value.to_string() == "value10");
+        // First match in insertion order
+        assert_eq!(*result.unwrap().value(), "value5");
+
+        let newest = cache.get_by_matcher_ordered(&matcher, MatchOrder::Newest);
+        assert_eq!(*newest.unwrap().value(), "value10");
+    }
+
+    #[test]
+    fn test_sorted_matcher_results() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(10));
+        cache.enable_key_index();
+
+        cache.insert_with_ttl(30, "c", Duration::from_secs(5));
+        cache.insert_with_ttl(10, "a", Duration::from_secs(20));
+        cache.insert_with_ttl(20, "b", Duration::from_secs(1));
+        cache.insert(40, "d");
+
+        let matcher = RangeMatcher::new(0, 100);
+        let keys = |entries: Vec<(&i32, &SimpleCacheObject<&str>)>| -> Vec<i32> {
+            entries.into_iter().map(|(k, _)| *k).collect()
+        };
+
+        // The key index returns key order, insertion order is still available
+        assert_eq!(keys(cache.get_all_by_matcher(&matcher)), [10, 20, 30, 40]);
+        assert_eq!(
+            keys(cache.get_all_by_matcher_sorted(&matcher, MatchOrder::Insertion, None)),
+            [30, 10, 20, 40]
+        );
+        assert_eq!(
+            keys(cache.get_all_by_matcher_sorted(&matcher, MatchOrder::Oldest, Some(2))),
+            [30, 10]
+        );
+        assert_eq!(
+            keys(cache.get_all_by_matcher_sorted(&matcher, MatchOrder::LongestTtl, None)),
+            [10, 40, 30, 20]
+        );
+        assert_eq!(
+            keys(cache.get_all_by_matcher_sorted(&matcher, MatchOrder::ShortestTtl, Some(1))),
+            [20]
+        );
+        assert_eq!(
+            keys(cache.get_all_by_matcher_sorted_by(&matcher, |a, b| b.0.cmp(a.0), Some(3))),
+            [40, 30, 20]
+        );
+
+        let oldest = cache.get_by_matcher_ordered(&matcher, MatchOrder::Oldest);
+        assert_eq!(*oldest.unwrap().value(), "c");
+        let smallest_value = cache.get_by_matcher_by(&matcher, |a, b| a.1.value().cmp(b.1.value()));
+        assert_eq!(*smallest_value.unwrap().value(), "a");
+        assert!(cache
+            .get_by_matcher_ordered(&RangeMatcher::new(50, 60), MatchOrder::Newest)
+            .is_err());
     }
 
     #[test]
@@ -1355,4 +4212,992 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap().value().to_string(), "even");
     }
+
+    #[test]
+    fn test_key_index_prefix_matcher() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(10), 4);
+        cache.insert("user:carol".to_string(), 3);
+        cache.enable_key_index();
+        cache.insert("user:bob".to_string(), 2);
+        cache.insert("admin:dave".to_string(), 4);
+        cache.insert("user:alice".to_string(), 1);
+        cache.insert("users".to_string(), 5); // Should evict user:carol
+
+        let matcher = PrefixMatcher::new("user:");
+        let keys: Vec<_> = cache
+            .get_all_by_matcher(&matcher)
+            .into_iter()
+            .map(|(key, _)| key.clone())
+            .collect();
+        assert_eq!(keys, vec!["user:alice", "user:bob"]);
+        assert_eq!(*cache.get_by_matcher(&matcher).unwrap().value(), 1);
+
+        cache.remove(&"user:alice".to_string());
+        assert_eq!(*cache.get_by_matcher(&matcher).unwrap().value(), 2);
+
+        cache.clear();
+        assert!(cache.get_by_matcher(&matcher).is_err());
+    }
+
+    #[test]
+    fn test_key_index_skips_expired() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(10));
+        cache.enable_key_index();
+        cache.insert_with_ttl("a:1".to_string(), 1, Duration::from_millis(50));
+        cache.insert("a:2".to_string(), 2);
+        cache.insert_with_ttl("b:1".to_string(), 3, Duration::from_millis(50));
+
+        thread::sleep(Duration::from_millis(100));
+
+        let matcher = PrefixMatcher::new("a:");
+        assert_eq!(*cache.get_by_matcher(&matcher).unwrap().value(), 2);
+        // Only the expired entry inside the queried range is cleaned up
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_all_by_matcher(&matcher).len(), 1);
+    }
+
+    #[test]
+    fn test_range_query() {
+        let mut plain = SimpleCacher::new(Duration::from_secs(10));
+        let mut indexed = SimpleCacher::new(Duration::from_secs(10));
+        indexed.enable_key_index();
+
+        for cache in [&mut plain, &mut indexed] {
+            for key in [15, 1, 10, 5, 20] {
+                cache.insert(key, key * 10);
+            }
+            cache.insert_with_ttl(12, 120, Duration::from_millis(50));
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        for cache in [&mut plain, &mut indexed] {
+            let keys: Vec<i32> = cache.range(5..=15).into_iter().map(|(k, _)| *k).collect();
+            assert_eq!(keys, vec![5, 10, 15]);
+
+            let keys: Vec<i32> = cache.range(..5).into_iter().map(|(k, _)| *k).collect();
+            assert_eq!(keys, vec![1]);
+            assert!(cache.range(7..7).is_empty());
+            assert_eq!(cache.len(), 5);
+
+            let matcher = RangeMatcher::exclusive(5, 20);
+            let keys: Vec<i32> = cache
+                .get_all_by_matcher(&matcher)
+                .into_iter()
+                .map(|(k, _)| *k)
+                .collect();
+            assert_eq!(keys.len(), 2);
+            assert!(keys.contains(&10) && keys.contains(&15));
+        }
+
+        // With the index, range matchers return keys in ascending order
+        let matcher = RangeMatcher::new(1, 20);
+        assert_eq!(*indexed.get_by_matcher(&matcher).unwrap().value(), 10);
+        let keys: Vec<i32> = indexed
+            .get_all_by_matcher(&matcher)
+            .into_iter()
+            .map(|(k, _)| *k)
+            .collect();
+        assert_eq!(keys, vec![1, 5, 10, 15, 20]);
+    }
+
+    #[test]
+    fn test_tag_invalidation() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(10), 4);
+
+        cache.insert_tagged("page:1", 1, &["user:42", "org:7"]);
+        cache.insert_tagged("page:2", 2, &["user:42"]);
+        cache.insert_tagged("page:3", 3, &["org:7", "org:7"]);
+        cache.insert("page:4", 4);
+
+        assert_eq!(cache.tags(&"page:3"), ["org:7"]);
+        let org: Vec<_> = cache
+            .get_all_by_tag("org:7")
+            .into_iter()
+            .map(|(k, _)| *k)
+            .collect();
+        assert_eq!(org, vec!["page:1", "page:3"]);
+
+        // Replacing without tags detaches the old ones
+        cache.insert("page:2", 20);
+        assert!(cache.tags(&"page:2").is_empty());
+
+        // Evicting page:1 drops it from both tags
+        cache.insert("page:5", 5);
+        assert_eq!(cache.get_all_by_tag("user:42").len(), 0);
+
+        assert_eq!(cache.invalidate_tag("org:7"), 1);
+        assert_eq!(cache.invalidate_tag("org:7"), 0);
+        assert_eq!(cache.len(), 3);
+        assert!(cache.contains_key(&"page:2"));
+    }
+
+    #[test]
+    fn test_tags_follow_expiry() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(10));
+        cache.insert_tagged_with_ttl(1, "short", Duration::from_millis(50), &["t"]);
+        cache.insert_tagged(2, "long", &["t"]);
+
+        thread::sleep(Duration::from_millis(100));
+
+        let tagged = cache.get_all_by_tag("t");
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(*tagged[0].0, 2);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.tags(&1).is_empty());
+    }
+
+    #[test]
+    fn test_dependency_cascade() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(10));
+
+        cache.insert("profile", 1);
+        cache.insert_with_dependencies("page", 2, &["profile", "theme"]);
+        cache.insert_with_dependencies("fragment", 3, &["page"]);
+        cache.insert("unrelated", 4);
+        assert_eq!(cache.dependencies(&"page"), ["profile", "theme"]);
+
+        // Replacing a parent cascades through the whole chain
+        cache.insert("profile", 10);
+        assert!(!cache.contains_key(&"page"));
+        assert!(!cache.contains_key(&"fragment"));
+        assert_eq!(cache.len(), 2);
+
+        // Parents that aren't cached yet still count
+        cache.insert_with_dependencies("page", 2, &["theme"]);
+        assert!(cache.remove(&"theme").is_none());
+        assert!(!cache.contains_key(&"page"));
+
+        // Dependencies don't outlive the dependent entry
+        cache.insert_with_dependencies("page", 2, &["profile"]);
+        cache.insert("page", 3);
+        assert!(cache.dependencies(&"page").is_empty());
+        cache.remove(&"profile");
+        assert!(cache.contains_key(&"page"));
+    }
+
+    #[test]
+    fn test_dependency_cycles_and_tags() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(10));
+
+        cache.insert_tagged(1, "a", &["group"]);
+        cache.insert(2, "b");
+        cache.insert(3, "c");
+        assert!(cache.add_dependency(&2, &1));
+        assert!(cache.add_dependency(&1, &2));
+        assert!(cache.add_dependency(&3, &2));
+        assert!(!cache.add_dependency(&4, &1));
+
+        // The cycle and its dependents are counted once each
+        assert_eq!(cache.invalidate_tag("group"), 3);
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 10);
+        cache.insert("a".to_string(), 1);
+        cache.insert_tagged("b".to_string(), 2, &["group"]);
+        cache.insert_with_dependencies("c".to_string(), 3, &["a".to_string()]);
+        cache.insert_with_ttl("d".to_string(), 4, Duration::from_millis(50));
+
+        thread::sleep(Duration::from_millis(100));
+        let json = serde_json::to_string(&cache).unwrap();
+        let mut restored: SimpleCacher<String, i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.stats().max_size, Some(10));
+        assert_eq!(restored.stats().max_age, Some(Duration::from_secs(60)));
+        let keys: Vec<_> = restored.iter_active().map(|(k, _)| k.clone()).collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+        assert!(restored.get(&"b".to_string()).unwrap().age() >= Duration::from_millis(100));
+        assert!(matches!(
+            restored.get(&"d".to_string()),
+            Err(SimpleCacheError::Expired { .. })
+        ));
+
+        assert_eq!(restored.tags(&"b".to_string()), ["group"]);
+        restored.insert("a".to_string(), 10);
+        assert!(!restored.contains_key(&"c".to_string()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_object_rebases_age() {
+        let obj = SimpleCacheObject::new("value", Some(Duration::from_secs(5)));
+        let json = serde_json::to_value(&obj).unwrap();
+        assert_eq!(json["value"], "value");
+
+        let created = std::time::SystemTime::now() - Duration::from_secs(10);
+        let old =
+            SimpleCacheObject::from_wall_clock("value", Some(Duration::from_secs(5)), created);
+        assert!(old.is_expired());
+        assert!(old.age() >= Duration::from_secs(10));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_save_and_load_snapshot() {
+        let path = std::env::temp_dir().join(format!(
+            "simple_cacher_snapshot_{}.snap",
+            std::process::id()
+        ));
+
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 5);
+        cache.insert_tagged(1u32, "one".to_string(), &["odd"]);
+        cache.insert_with_ttl(2, "two".to_string(), Duration::from_millis(100));
+        cache.insert_with_ttl(3, "three".to_string(), Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(30));
+
+        // The already expired entry is skipped
+        assert_eq!(cache.save_to(&path).unwrap(), 2);
+        thread::sleep(Duration::from_millis(100));
+
+        // Entry 2 expired while on disk and is dropped on load
+        let mut restored: SimpleCacher<u32, String> = SimpleCacher::load_from(&path).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored.stats().max_size, Some(5));
+        assert!(restored.get(&1).unwrap().age() >= Duration::from_millis(130));
+        assert_eq!(restored.invalidate_tag("odd"), 1);
+
+        std::fs::write(&path, b"garbage!").unwrap();
+        let err = SimpleCacher::<u32, String>::load_from(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::write(&path, b"SCSN\x09\x00\x00\x00").unwrap();
+        let err = SimpleCacher::<u32, String>::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("version 9"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_snapshot_keeps_longest_ttl() {
+        use std::time::SystemTime;
+
+        let path = std::env::temp_dir().join(format!(
+            "simple_cacher_snapshot_ttl_{}.snap",
+            std::process::id()
+        ));
+
+        // An explicit TTL of Duration::MAX still expires after a reload
+        let mut cache = SimpleCacher::without_expiry();
+        cache.insert_with_ttl(1u32, "one".to_string(), Duration::MAX);
+        cache.insert(2, "two".to_string());
+        cache.save_to(&path).unwrap();
+
+        let mut restored: SimpleCacher<u32, String> = SimpleCacher::load_from(&path).unwrap();
+        assert_eq!(restored.stats().max_age, None);
+        assert!(restored.get(&1).unwrap().ttl_remaining().is_some());
+        assert_eq!(restored.get(&2).unwrap().ttl_remaining(), None);
+
+        // Version 1 snapshots stored "never expires" as Duration::MAX
+        let created = SystemTime::now();
+        let no_tags: Vec<String> = Vec::new();
+        let no_deps: Vec<u32> = Vec::new();
+        let entries = vec![
+            (1u32, ("one", Duration::MAX, created), &no_tags, &no_deps),
+            (
+                2,
+                ("two", Duration::from_secs(60), created),
+                &no_tags,
+                &no_deps,
+            ),
+        ];
+        let mut bytes = b"SCSN\x01\x00\x00\x00".to_vec();
+        bincode::serialize_into(&mut bytes, &(Duration::MAX, Some(5usize), entries)).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut restored: SimpleCacher<u32, String> = SimpleCacher::load_from(&path).unwrap();
+        let stats = restored.stats();
+        assert_eq!((stats.max_age, stats.max_size), (None, Some(5)));
+        assert_eq!(restored.get(&1).unwrap().ttl_remaining(), None);
+        assert!(restored.get(&2).unwrap().ttl_remaining().is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_negative_caching() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&loads);
+        let mut cache = SimpleCacher::new(Duration::from_secs(60));
+        cache.set_loader(move |key: &u32| -> std::io::Result<u32> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(key * 10)
+        });
+
+        cache.insert(1, 10);
+        cache.insert_with_dependencies(2, 20, &[1]);
+
+        // Recording a miss drops the cached value and its dependents
+        cache.insert_negative(1, Duration::from_millis(30));
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1).unwrap_err(), SimpleCacheError::NegativeHit);
+        assert_eq!(
+            cache.get_or_load(&1).unwrap_err(),
+            SimpleCacheError::NegativeHit
+        );
+        assert!(matches!(
+            cache.get_mut_or_load(&1),
+            Err(SimpleCacheError::NegativeHit)
+        ));
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+        assert!(cache.is_negative(&1));
+
+        // Once the negative entry expires the loader is used again
+        thread::sleep(Duration::from_millis(50));
+        assert!(!cache.is_negative(&1));
+        assert_eq!(cache.get_or_load(&1).unwrap().value(), &10);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.negative_len(), 0);
+
+        cache.insert_negative(3, Duration::from_millis(10));
+        cache.insert_negative(4, Duration::from_secs(60));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.cleanup_expired(), 0);
+        assert_eq!(cache.negative_len(), 1);
+
+        cache.remove(&4);
+        assert_eq!(cache.get_or_load(&4).unwrap().value(), &40);
+
+        // Layered caches don't consult the backend for known misses
+        let mut layered = LayeredCacher::new(SimpleCacher::new(Duration::from_secs(60)), {
+            let mut backend = std::collections::HashMap::new();
+            backend.insert(5, 50);
+            backend
+        });
+        layered
+            .cache_mut()
+            .insert_negative(5, Duration::from_secs(60));
+        assert!(layered.get(&5).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_negative_capacity() {
+        // A flood of misses only keeps the newest ones, up to the size limit
+        let mut cache: SimpleCacher<u32, u32> =
+            SimpleCacher::with_max_size(Duration::from_secs(60), 3);
+        for key in 0..100 {
+            cache.insert_negative(key, Duration::from_secs(60));
+        }
+        assert_eq!(cache.negative_len(), 3);
+        assert!(!cache.is_negative(&96));
+        assert!((97..100).all(|key| cache.is_negative(&key)));
+
+        // Recording a key again makes it the newest
+        cache.insert_negative(97, Duration::from_secs(60));
+        cache.insert_negative(100, Duration::from_secs(60));
+        assert!(!cache.is_negative(&98));
+        assert!(cache.is_negative(&97));
+
+        // An explicit limit applies to unlimited caches too
+        let mut unlimited: SimpleCacher<u32, u32> = SimpleCacher::new(Duration::from_secs(60));
+        for key in 0..10 {
+            unlimited.insert_negative(key, Duration::from_secs(60));
+        }
+        assert_eq!(unlimited.negative_len(), 10);
+        unlimited.set_max_negative_size(4);
+        assert_eq!(unlimited.negative_len(), 4);
+        assert!(unlimited.is_negative(&6));
+        unlimited.set_max_negative_size(0);
+        unlimited.insert_negative(10, Duration::from_secs(60));
+        assert_eq!(unlimited.negative_len(), 0);
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut cache = SimpleCacher::new(Duration::from_millis(10));
+        cache.insert(1, "one");
+        thread::sleep(Duration::from_millis(30));
+        match cache.get(&1) {
+            Err(SimpleCacheError::Expired { expired_for }) => {
+                assert!(expired_for >= Duration::from_millis(20));
+                assert!(expired_for < Duration::from_secs(5));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // A zero-sized cache rejects inserts and loads
+        let mut empty = SimpleCacher::with_max_size(Duration::from_secs(60), 0);
+        assert_eq!(
+            empty.try_insert(1, "one"),
+            Err(SimpleCacheError::CapacityRejected { max_size: 0 })
+        );
+        let inserted =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| empty.insert(2, "two")));
+        assert!(inserted.is_err());
+        assert!(empty.is_empty());
+        empty.set_loader(|_: &u32| -> std::io::Result<&'static str> { Ok("loaded") });
+        assert!(matches!(
+            empty.get_or_load(&3),
+            Err(SimpleCacheError::CapacityRejected { .. })
+        ));
+
+        let lock = Arc::new(std::sync::Mutex::new(SimpleCacher::<u32, u32>::new(
+            Duration::from_secs(60),
+        )));
+        let poisoner = Arc::clone(&lock);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        let locked: Result<(), SimpleCacheError> = lock.lock().map(|_| ()).map_err(Into::into);
+        assert_eq!(locked, Err(SimpleCacheError::LockPoisoned));
+        assert_eq!(
+            SimpleCacheError::LockPoisoned.to_string(),
+            "Cache lock was poisoned"
+        );
+    }
+
+    #[test]
+    fn test_stale_fallback() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut cache = SimpleCacher::new(Duration::from_millis(20));
+        cache.insert("a", 1);
+        cache.insert_with_dependencies("b", 2, &["a"]);
+
+        assert!(!cache.get_or_take_expired(&"a").unwrap().is_stale());
+        assert_eq!(
+            cache.get_or_take_expired(&"zzz").unwrap_err(),
+            SimpleCacheError::NotFound
+        );
+
+        // Without a loader the expired value is handed back and removed
+        thread::sleep(Duration::from_millis(40));
+        let taken = cache.get_or_take_expired(&"a").unwrap();
+        assert!(taken.is_stale());
+        assert_eq!(*taken.value(), 1);
+        assert!(taken.entry().is_expired());
+        assert_eq!(cache.len(), 1);
+
+        // With a loader the stale value is only a fallback
+        let origin_up = Arc::new(AtomicBool::new(false));
+        let up = Arc::clone(&origin_up);
+        cache.set_loader(move |_: &&str| -> std::io::Result<i32> {
+            if up.load(Ordering::SeqCst) {
+                Ok(20)
+            } else {
+                Err(std::io::Error::other("origin down"))
+            }
+        });
+        assert_eq!(*cache.get_or_load_or_take_expired(&"b").unwrap().value(), 2);
+        assert!(cache.is_empty());
+
+        cache.insert("b", 3);
+        thread::sleep(Duration::from_millis(40));
+        origin_up.store(true, Ordering::SeqCst);
+        let fresh = cache.get_or_load_or_take_expired(&"b").unwrap();
+        assert!(!fresh.is_stale());
+        assert_eq!(*fresh.value(), 20);
+    }
+
+    #[test]
+    fn test_stale_fallback_keeps_changes() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Mutex;
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let store_up = Arc::new(AtomicBool::new(false));
+        let mut cache = SimpleCacher::new(Duration::from_millis(20));
+        let (log, up) = (Arc::clone(&written), Arc::clone(&store_up));
+        cache.set_writer(move |key: &u32, value: &u32| -> std::io::Result<()> {
+            if !up.load(Ordering::SeqCst) {
+                return Err(std::io::Error::other("store down"));
+            }
+            log.lock().unwrap().push((*key, *value));
+            Ok(())
+        });
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: None,
+        });
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        thread::sleep(Duration::from_millis(40));
+
+        // A failed write keeps the change queued instead of handing it out
+        assert!(matches!(
+            cache.get_or_take_expired(&1),
+            Err(SimpleCacheError::WriteFailed(_))
+        ));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.dirty_len(), 2);
+
+        store_up.store(true, Ordering::SeqCst);
+        let taken = cache.get_or_take_expired(&2).unwrap();
+        assert!(taken.is_stale());
+        assert_eq!(*taken.value(), 20);
+        assert_eq!(*written.lock().unwrap(), [(2, 20)]);
+        assert_eq!(cache.flush().unwrap(), 1);
+        assert_eq!(*written.lock().unwrap(), [(2, 20), (1, 10)]);
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct Route {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Clone, Hash)]
+        struct RouteRef<'a> {
+            host: &'a str,
+            port: u16,
+        }
+
+        impl Equivalent<Route> for RouteRef<'_> {
+            fn equivalent(&self, key: &Route) -> bool {
+                self.host == key.host && self.port == key.port
+            }
+        }
+
+        let mut names = SimpleCacher::new(Duration::from_secs(60));
+        names.insert("user:1".to_string(), "Alice");
+        assert_eq!(*names.get("user:1").unwrap().value(), "Alice");
+        *names.get_mut("user:1").unwrap().value_mut() = "Alicia";
+        assert!(names.contains_key("user:1"));
+        assert_eq!(names.remove("user:1").unwrap().into_value(), "Alicia");
+
+        let mut routes = SimpleCacher::new(Duration::from_secs(60));
+        let route = Route {
+            host: "example.com".to_string(),
+            port: 443,
+        };
+        routes.insert(route.clone(), "backend-a");
+        let view = RouteRef {
+            host: "example.com",
+            port: 443,
+        };
+        assert_eq!(*routes.get(&view).unwrap().value(), "backend-a");
+        assert!(!routes.contains_key(&RouteRef {
+            port: 80,
+            ..view.clone()
+        }));
+
+        // Borrowed lookups don't load; loading takes the owned key
+        routes.set_loader(|route: &Route| -> std::io::Result<&'static str> {
+            Ok(if route.port == 80 {
+                "redirect"
+            } else {
+                "backend-b"
+            })
+        });
+        let plain = RouteRef { port: 80, ..view };
+        assert_eq!(routes.get(&plain).unwrap_err(), SimpleCacheError::NotFound);
+        let owned = Route { port: 80, ..route };
+        assert_eq!(*routes.get_or_load(&owned).unwrap().value(), "redirect");
+        assert!(routes.remove(&plain).is_some());
+        assert_eq!(routes.len(), 1);
+    }
+
+    #[test]
+    fn test_non_clone_keys() {
+        use std::borrow::Borrow;
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Handle(u32);
+
+        impl Borrow<u32> for Handle {
+            fn borrow(&self) -> &u32 {
+                &self.0
+            }
+        }
+
+        let mut cache = SimpleCacher::new(Duration::from_secs(60));
+        cache.insert(Handle(1), "one");
+        cache.insert_with_ttl(Handle(2), "two", Duration::from_millis(10));
+        cache.insert(Handle(3), "three");
+        thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(*cache.get(&1).unwrap().value(), "one");
+        assert!(!cache.get_or_take_expired(&3).unwrap().is_stale());
+        let results = cache.get_many([&1, &2]);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(SimpleCacheError::Expired { .. })));
+        assert!(cache.contains_key(&Handle(3)));
+        let odd = FnMatcher::new(|key: &Handle| key.0 % 2 == 1);
+        assert_eq!(cache.get_all_by_matcher(&odd).len(), 2);
+        assert_eq!(cache.len(), 2);
+
+        cache.insert_with_ttl(Handle(4), "four", Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(30));
+        let even = FnMatcher::new(|key: &Handle| key.0 % 2 == 0);
+        assert!(cache.get_by_matcher(&even).is_err());
+        assert_eq!(cache.cleanup_expired(), 0);
+
+        // Dirty entries are tracked without copying their keys
+        let written = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&written);
+        cache.set_writer(move |key: &Handle, _: &&str| -> std::io::Result<()> {
+            sink.lock().unwrap().push(key.0);
+            Ok(())
+        });
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: None,
+        });
+        cache.insert_with_ttl(Handle(5), "five", Duration::from_millis(10));
+        *cache.get_mut(&1).unwrap().value_mut() = "uno";
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.cleanup_expired(), 1);
+        assert_eq!(cache.dirty_len(), 2);
+        assert_eq!(cache.flush().unwrap(), 2);
+        assert_eq!(*written.lock().unwrap(), [5, 1]);
+        assert_eq!(cache.remove(&3).unwrap().into_value(), "three");
+
+        // Indexed lookups skip expired matches that come first in key order
+        let mut users = SimpleCacher::new(Duration::from_secs(60));
+        users.enable_key_index();
+        users.insert_with_ttl("user:a".to_string(), 1, Duration::from_millis(10));
+        users.insert("user:b".to_string(), 2);
+        thread::sleep(Duration::from_millis(30));
+        let found = users.get_by_matcher(&PrefixMatcher::new("user:")).unwrap();
+        assert_eq!(*found.value(), 2);
+        assert_eq!(users.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_in_full_cache() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 2);
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+
+        // Refreshing a key in a full cache doesn't evict anything
+        assert_eq!(cache.insert(1, "a2"), Some("a"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains_key(&2));
+
+        assert_eq!(cache.replace(2, "b2"), Some("b"));
+        assert_eq!(cache.replace(3, "c"), None);
+        assert!(!cache.contains_key(&3));
+
+        // The replaced key keeps its place in the eviction order
+        cache.insert(3, "c");
+        assert!(!cache.contains_key(&1));
+        assert_eq!(*cache.get(&2).unwrap().value(), "b2");
+
+        // Expired previous values aren't reported
+        cache.insert_with_ttl(4, "d", Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.replace(4, "d2"), None);
+        assert_eq!(cache.insert(4, "d2"), None);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_returning_evicted() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Mutex;
+
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 2);
+        assert!(cache.insert_returning_evicted(1, "a").unwrap().is_empty());
+        assert!(cache.insert_returning_evicted(2, "b").unwrap().is_empty());
+        assert!(cache.insert_returning_evicted(2, "b2").unwrap().is_empty());
+
+        let evicted = cache
+            .insert_with_ttl_returning_evicted(3, "c", Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, 1);
+        assert_eq!(*evicted[0].1.value(), "a");
+        assert_eq!(cache.len(), 2);
+
+        let mut empty = SimpleCacher::with_max_size(Duration::from_secs(60), 0);
+        assert_eq!(
+            empty.insert_returning_evicted(1, "a").unwrap_err(),
+            SimpleCacheError::CapacityRejected { max_size: 0 }
+        );
+
+        // Dirty entries are written before they are handed out
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let fail = Arc::new(AtomicBool::new(false));
+        let (sink, failing) = (Arc::clone(&written), Arc::clone(&fail));
+        cache.set_writer(move |key: &u32, _: &&str| -> std::io::Result<()> {
+            if failing.load(Ordering::SeqCst) {
+                return Err(std::io::Error::other("store down"));
+            }
+            sink.lock().unwrap().push(*key);
+            Ok(())
+        });
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: None,
+        });
+        cache.insert(4, "d");
+        cache.insert(5, "e");
+        assert_eq!(cache.insert_returning_evicted(6, "f").unwrap()[0].0, 4);
+        assert_eq!(*written.lock().unwrap(), [4]);
+        assert_eq!(cache.dirty_len(), 2);
+
+        // If that fails they stay queued instead
+        fail.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            cache.insert_returning_evicted(7, "g"),
+            Err(SimpleCacheError::WriteFailed(_))
+        ));
+        assert!(cache.contains_key(&7));
+        assert_eq!(cache.dirty_len(), 3);
+        fail.store(false, Ordering::SeqCst);
+        assert_eq!(cache.flush().unwrap(), 3);
+        assert_eq!(*written.lock().unwrap(), [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_batch_operations() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 4);
+        cache
+            .insert_many([(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")])
+            .unwrap();
+        assert_eq!(cache.len(), 4);
+        assert!(!cache.contains_key(&1));
+
+        // Replacements in a batch don't count against the limit but keep
+        // their place in the eviction order
+        cache.insert_many([(3, "c2"), (6, "f")]).unwrap();
+        assert_eq!(cache.len(), 4);
+        assert!(!cache.contains_key(&2));
+        assert_eq!(*cache.get(&3).unwrap().value(), "c2");
+
+        cache.insert_with_ttl(7, "g", Duration::from_millis(10));
+        cache.insert_with_ttl(5, "e", Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(30));
+        let results = cache.get_many([&6, &5, &1, &6, &7]);
+        assert_eq!(*results[0].as_ref().unwrap().value(), "f");
+        assert!(matches!(results[1], Err(SimpleCacheError::Expired { .. })));
+        assert_eq!(
+            results[2].as_ref().unwrap_err(),
+            &SimpleCacheError::NotFound
+        );
+        assert_eq!(*results[3].as_ref().unwrap().value(), "f");
+        assert!(matches!(results[4], Err(SimpleCacheError::Expired { .. })));
+        assert_eq!(cache.len(), 2);
+
+        // Misses are loaded once, even when a key is repeated
+        let loads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&loads);
+        cache.set_loader(move |key: &i32| -> std::io::Result<&'static str> {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(if *key == 8 { "h" } else { "?" })
+        });
+        cache.insert_negative(9, Duration::from_secs(60));
+        let results = cache.get_many_or_load([&8, &9, &8]);
+        assert_eq!(*results[0].as_ref().unwrap().value(), "h");
+        assert_eq!(
+            results[1].as_ref().unwrap_err(),
+            &SimpleCacheError::NegativeHit
+        );
+        assert_eq!(*results[2].as_ref().unwrap().value(), "h");
+        assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // Removal cascades to dependents and drops negative entries
+        let mut deps = SimpleCacher::new(Duration::from_secs(60));
+        deps.insert_many((0..6).map(|i| (i, i * 10))).unwrap();
+        deps.add_dependency(&5, &0);
+        deps.insert_negative(9, Duration::from_secs(60));
+        let removed = deps.remove_many([&3, &0, &9, &3]);
+        let keys: Vec<i32> = removed.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [0, 3]);
+        assert_eq!(deps.len(), 3);
+        assert!(!deps.contains_key(&5));
+        assert!(!deps.is_negative(&9));
+        let left: Vec<i32> = deps.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(left, [1, 2, 4]);
+    }
+
+    #[test]
+    fn test_eviction_order_after_removals() {
+        let mut cache = SimpleCacher::with_max_size(Duration::from_secs(60), 3);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(3, "c");
+        cache.remove(&2);
+        cache.insert(4, "d");
+        cache.insert(2, "b2");
+
+        // 1 was still the oldest entry and got evicted; the re-inserted 2 is the newest
+        let keys: Vec<i32> = cache.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [3, 4, 2]);
+        let evicted = cache.insert_returning_evicted(5, "e").unwrap();
+        assert_eq!(evicted[0].0, 3);
+    }
+
+    #[test]
+    fn test_entry_metadata() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(60));
+        cache.set_loader(|key: &u32| -> std::io::Result<u32> { Ok(key * 10) });
+        cache.insert(1, 1);
+
+        let entry = cache.get(&1).unwrap();
+        assert_eq!(entry.source(), EntrySource::Inserted);
+        assert_eq!(entry.access_count(), 1);
+        assert_eq!(entry.last_modified(), entry.created_at());
+        let first_access = entry.last_accessed().unwrap();
+
+        thread::sleep(Duration::from_millis(5));
+        let entry = cache.get_mut(&1).unwrap();
+        *entry.value_mut() += 1;
+        entry.set_metadata("pinned");
+        assert!(entry.last_modified() > entry.created_at());
+
+        let results = cache.get_many_or_load([&1, &2, &1]);
+        let entry = results[0].as_ref().unwrap();
+        assert_eq!(entry.access_count(), 4);
+        assert!(entry.last_accessed().unwrap() > first_access);
+        assert_eq!(entry.metadata::<&str>(), Some(&"pinned"));
+        assert_eq!(entry.metadata::<u32>(), None);
+
+        // Loading counts as the first access of the new entry
+        let loaded = results[1].as_ref().unwrap();
+        assert_eq!(loaded.source(), EntrySource::Loaded);
+        assert_eq!(loaded.access_count(), 1);
+
+        // Replacing an entry starts its statistics over
+        cache.insert(1, 5);
+        let entry = cache.get_mut(&1).unwrap();
+        assert_eq!(entry.access_count(), 1);
+        assert_eq!(entry.metadata::<&str>(), None);
+        entry.set_metadata(7u8);
+        entry.clear_metadata();
+        assert_eq!(entry.metadata::<u8>(), None);
+
+        // Only key lookups count as accesses
+        cache.get_by_matcher(&ExactMatcher::new(2)).unwrap();
+        assert_eq!(cache.remove(&2).unwrap().access_count(), 1);
+    }
+
+    #[test]
+    fn test_update_ttl() {
+        let mut cache = SimpleCacher::new(Duration::from_millis(100));
+        for (key, value) in [
+            (0, "zero"),
+            (1, "one"),
+            (2, "two"),
+            (3, "three"),
+            (4, "four"),
+        ] {
+            cache.insert(key, value);
+        }
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(cache.touch(&1));
+        assert!(cache.set_ttl(&2, Duration::from_millis(500)));
+        assert!(cache.persist(&3));
+        assert!(cache.expire_at(&4, Instant::now() + Duration::from_millis(500)));
+        assert!(!cache.touch(&5) && !cache.set_ttl(&5, Duration::ZERO));
+
+        // Only the untouched entry has run out
+        thread::sleep(Duration::from_millis(60));
+        let keys: Vec<i32> = cache.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [1, 2, 3, 4]);
+        thread::sleep(Duration::from_millis(60));
+        let keys: Vec<i32> = cache.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [2, 3, 4]);
+
+        // Expired entries can't be revived
+        assert!(!cache.persist(&1) && !cache.touch(&0));
+        assert_eq!(cache.len(), 3);
+
+        assert!(!cache.persist(&3));
+        assert!(cache.set_ttl(&3, Duration::ZERO));
+        assert!(cache.expire_at(&4, Instant::now() - Duration::from_millis(1)));
+        let keys: Vec<i32> = cache.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [2]);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_no_expiry() {
+        let mut cache = SimpleCacher::with_max_size_without_expiry(3);
+        cache.insert(1, "one");
+        cache.insert_with_ttl(2, "two", Duration::from_millis(20));
+        cache.insert_tagged(3, "three", &["group"]);
+
+        thread::sleep(Duration::from_millis(40));
+        let stats = cache.stats();
+        assert_eq!((stats.active_entries, stats.expired_entries), (2, 1));
+        assert_eq!(stats.persistent_entries, 2);
+        assert_eq!(stats.max_age, None);
+        assert_eq!(cache.cleanup_expired(), 1);
+        assert!(!cache.get(&1).unwrap().is_expired());
+
+        // Eviction still applies
+        cache.insert(4, "four");
+        cache.insert(5, "five");
+        let keys: Vec<i32> = cache.iter_active().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [3, 4, 5]);
+
+        let mut cache = SimpleCacher::new(Duration::from_millis(20));
+        cache.insert_without_expiry("pinned", 1);
+        cache.insert("temporary", 2);
+        thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.stats().persistent_entries, 1);
+        assert_eq!(cache.cleanup_expired(), 1);
+        assert!(cache.set_ttl(&"pinned", Duration::from_secs(60)));
+        assert_eq!(cache.stats().persistent_entries, 0);
+    }
+
+    #[test]
+    fn test_expiry_queries() {
+        let mut cache = SimpleCacher::new(Duration::from_secs(60));
+        assert_eq!(cache.next_expiry(), None);
+
+        cache.insert_without_expiry(1, "one");
+        assert_eq!(cache.next_expiry(), None);
+        let entry = cache.get(&1).unwrap();
+        assert_eq!((entry.ttl_remaining(), entry.expires_at()), (None, None));
+
+        cache.insert(2, "two");
+        cache.insert_with_ttl(3, "three", Duration::from_millis(20));
+        cache.insert_with_ttl(4, "four", Duration::MAX);
+        let entry = cache.get(&4).unwrap();
+        assert_eq!(entry.expires_at(), None);
+        assert!(entry.ttl_remaining().unwrap() > Duration::from_secs(60));
+
+        let entry = cache.get(&2).unwrap();
+        assert!(entry.ttl_remaining().unwrap() <= Duration::from_secs(60));
+        assert_eq!(
+            entry.expires_at(),
+            Some(entry.created_at() + Duration::from_secs(60))
+        );
+        let third = cache.get(&3).unwrap().expires_at();
+        assert_eq!(cache.next_expiry(), third);
+
+        // Expired entries report the past until they are cleaned up
+        thread::sleep(Duration::from_millis(30));
+        assert!(cache.next_expiry().unwrap() < Instant::now());
+        let (_, entry) = cache.cache.iter().nth(2).unwrap();
+        assert_eq!(entry.ttl_remaining(), Some(Duration::ZERO));
+        cache.cleanup_expired();
+        assert_eq!(cache.next_expiry(), cache.get(&2).unwrap().expires_at());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_no_expiry() {
+        let mut cache = SimpleCacher::without_expiry();
+        cache.insert("a".to_string(), 1);
+        cache.insert_with_ttl("b".to_string(), 2, Duration::from_secs(60));
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let restored: SimpleCacher<String, i32> = serde_json::from_str(&json).unwrap();
+        let stats = restored.stats();
+        assert_eq!(stats.max_age, None);
+        assert_eq!((stats.active_entries, stats.persistent_entries), (2, 1));
+    }
+
+    #[test]
+    fn test_prefix_upper_bound() {
+        assert_eq!(
+            index::prefix_upper_bound("user:"),
+            Some("user;".to_string())
+        );
+        assert_eq!(index::prefix_upper_bound(""), None);
+        assert_eq!(
+            index::prefix_upper_bound("a\u{10FFFF}"),
+            Some("b".to_string())
+        );
+    }
 }
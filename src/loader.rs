@@ -0,0 +1,544 @@
+//! Read-through loading and write-through persistence for `SimpleCacher`.
+
+use crate::store::Store;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Boxed error returned by type-erased loaders and writers.
+pub(crate) type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Loads values from the source of truth when a cache lookup misses.
+///
+/// Configure one with `SimpleCacher::set_loader`. Closures of the form
+/// `Fn(&T) -> Result<U, E>` implement this trait automatically.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::collections::HashMap;
+/// use std::io;
+///
+/// struct Database {
+///     users: HashMap<u32, String>,
+/// }
+///
+/// impl Loader<u32, String> for Database {
+///     type Error = io::Error;
+///
+///     fn load(&self, id: &u32) -> Result<String, io::Error> {
+///         self.users
+///             .get(id)
+///             .cloned()
+///             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such user"))
+///     }
+/// }
+/// ```
+pub trait Loader<T, U> {
+    /// Error returned when a value can't be loaded.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Loads the value for `key`.
+    fn load(&self, key: &T) -> Result<U, Self::Error>;
+}
+
+impl<T, U, E, F> Loader<T, U> for F
+where
+    F: Fn(&T) -> Result<U, E>,
+    E: Error + Send + Sync + 'static,
+{
+    type Error = E;
+
+    fn load(&self, key: &T) -> Result<U, E> {
+        self(key)
+    }
+}
+
+/// Writes inserted values through to the source of truth.
+///
+/// Configure one with `SimpleCacher::set_writer`. Closures of the form
+/// `Fn(&T, &U) -> Result<(), E>` implement this trait automatically.
+pub trait Writer<T, U> {
+    /// Error returned when a value can't be written.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Writes `value` for `key`.
+    fn write(&self, key: &T, value: &U) -> Result<(), Self::Error>;
+
+    /// Writes several entries at once.
+    ///
+    /// Used when flushing in `WriteMode::Behind`. The default implementation
+    /// calls `write` for each entry in order and stops at the first error;
+    /// override it to use the store's bulk API.
+    fn write_batch(&self, entries: &[(&T, &U)]) -> Result<(), Self::Error> {
+        for (key, value) in entries {
+            self.write(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, U, E, F> Writer<T, U> for F
+where
+    F: Fn(&T, &U) -> Result<(), E>,
+    E: Error + Send + Sync + 'static,
+{
+    type Error = E;
+
+    fn write(&self, key: &T, value: &U) -> Result<(), E> {
+        self(key, value)
+    }
+}
+
+/// When a configured `Writer` is called.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::io;
+/// use std::time::Duration;
+///
+/// let mut counters = SimpleCacher::new(Duration::from_secs(600));
+/// counters.set_writer(|key: &String, count: &u64| -> io::Result<()> {
+///     println!("{} = {}", key, count);
+///     Ok(())
+/// });
+/// counters.set_write_mode(WriteMode::Behind {
+///     flush_interval: Some(Duration::from_secs(10)),
+/// });
+///
+/// counters.insert("page:/home".to_string(), 0);
+/// for _ in 0..100 {
+///     *counters.get_mut(&"page:/home".to_string()).unwrap().value_mut() += 1;
+/// }
+///
+/// // One write for all 100 increments
+/// assert_eq!(counters.flush().unwrap(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Every insert is written immediately and only cached if the write
+    /// succeeds. This is the default.
+    Through,
+    /// Inserts and `get_mut` calls only mark entries dirty. Dirty entries are
+    /// written in one batch by `SimpleCacher::flush`, which also runs
+    /// automatically on insert once `flush_interval` has passed since the
+    /// last flush. The interval is only checked on insert, so a cache that
+    /// stops receiving inserts keeps its changes until `flush` is called.
+    Behind {
+        /// How often inserts trigger a flush, or `None` to only flush manually.
+        flush_interval: Option<Duration>,
+    },
+}
+
+/// Object-safe form of `Loader` with the error type erased.
+trait DynLoader<T, U>: Send + Sync {
+    fn load(&self, key: &T) -> Result<U, BoxError>;
+}
+
+impl<T, U, L> DynLoader<T, U> for L
+where
+    L: Loader<T, U> + Send + Sync,
+{
+    fn load(&self, key: &T) -> Result<U, BoxError> {
+        Loader::load(self, key).map_err(Into::into)
+    }
+}
+
+/// Object-safe form of `Writer` with the error type erased.
+trait DynWriter<T, U>: Send + Sync {
+    fn write(&self, key: &T, value: &U) -> Result<(), BoxError>;
+    fn write_batch(&self, entries: &[(&T, &U)]) -> Result<(), BoxError>;
+}
+
+impl<T, U, W> DynWriter<T, U> for W
+where
+    W: Writer<T, U> + Send + Sync,
+{
+    fn write(&self, key: &T, value: &U) -> Result<(), BoxError> {
+        Writer::write(self, key, value).map_err(Into::into)
+    }
+
+    fn write_batch(&self, entries: &[(&T, &U)]) -> Result<(), BoxError> {
+        Writer::write_batch(self, entries).map_err(Into::into)
+    }
+}
+
+/// The loader and writer configured on a cache, plus write-behind state.
+///
+/// The loader and writer are shared between clones of the cache; pending
+/// entries are copied. Whether a cached entry is dirty is tracked on the
+/// entry itself, so marking it doesn't need a copy of its key.
+pub(crate) struct Source<T, U> {
+    loader: Option<Arc<dyn DynLoader<T, U>>>,
+    writer: Option<Arc<dyn DynWriter<T, U>>>,
+    mode: WriteMode,
+    /// Dirty entries that left the cache before being written, with the
+    /// latest value for each key.
+    pending: Store<T, U>,
+    last_flush: Instant,
+}
+
+impl<T, U> Source<T, U> {
+    pub(crate) fn set_loader<L>(&mut self, loader: L)
+    where
+        L: Loader<T, U> + Send + Sync + 'static,
+    {
+        self.loader = Some(Arc::new(loader));
+    }
+
+    pub(crate) fn set_writer<W>(&mut self, writer: W)
+    where
+        W: Writer<T, U> + Send + Sync + 'static,
+    {
+        self.writer = Some(Arc::new(writer));
+    }
+
+    pub(crate) fn clear_loader(&mut self) {
+        self.loader = None;
+    }
+
+    pub(crate) fn clear_writer(&mut self) {
+        self.writer = None;
+    }
+
+    pub(crate) fn has_loader(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    pub(crate) fn has_writer(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Loads `key`, or returns `None` if no loader is configured.
+    pub(crate) fn load(&self, key: &T) -> Option<Result<U, BoxError>> {
+        self.loader.as_ref().map(|loader| loader.load(key))
+    }
+
+    /// Writes `value` for `key`; succeeds trivially if no writer is configured.
+    pub(crate) fn write(&self, key: &T, value: &U) -> Result<(), BoxError> {
+        match &self.writer {
+            Some(writer) => writer.write(key, value),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes a batch of entries; succeeds trivially if no writer is configured.
+    pub(crate) fn write_batch(&self, entries: &[(&T, &U)]) -> Result<(), BoxError> {
+        match &self.writer {
+            Some(writer) if !entries.is_empty() => writer.write_batch(entries),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn mode(&self) -> WriteMode {
+        self.mode
+    }
+
+    pub(crate) fn set_mode(&mut self, mode: WriteMode) {
+        self.mode = mode;
+        self.last_flush = Instant::now();
+    }
+
+    /// Returns `true` if writes should be deferred to the next flush.
+    pub(crate) fn is_write_behind(&self) -> bool {
+        self.writer.is_some() && matches!(self.mode, WriteMode::Behind { .. })
+    }
+
+    /// Returns `true` if the flush interval has passed since the last flush.
+    pub(crate) fn flush_due(&self) -> bool {
+        match self.mode {
+            WriteMode::Behind {
+                flush_interval: Some(interval),
+            } => self.last_flush.elapsed() >= interval,
+            _ => false,
+        }
+    }
+
+    /// Returns the entries that left the cache before being written.
+    pub(crate) fn pending(&self) -> impl Iterator<Item = (&T, &U)> + '_ {
+        self.pending.iter()
+    }
+
+    pub(crate) fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Records that the pending entries were written as part of a full flush.
+    pub(crate) fn mark_flushed(&mut self) {
+        self.pending.clear();
+        self.last_flush = Instant::now();
+    }
+
+    /// Records that the pending entries were written.
+    pub(crate) fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Discards all unwritten changes.
+    pub(crate) fn discard_changes(&mut self) {
+        self.pending.clear();
+    }
+}
+
+impl<T, U> Source<T, U>
+where
+    T: Eq + Hash,
+{
+    /// Keeps the value of a dirty entry leaving the cache for the next flush.
+    ///
+    /// A value already queued for the same key is replaced, and the key
+    /// moves to the end of the queue.
+    pub(crate) fn retire(&mut self, key: T, value: U) {
+        self.pending.remove(&key);
+        self.pending.insert(key, value);
+    }
+}
+
+impl<T, U> Default for Source<T, U> {
+    fn default() -> Self {
+        Self {
+            loader: None,
+            writer: None,
+            mode: WriteMode::Through,
+            pending: Store::new(),
+            last_flush: Instant::now(),
+        }
+    }
+}
+
+impl<T, U> Clone for Source<T, U>
+where
+    T: Clone,
+    U: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            loader: self.loader.clone(),
+            writer: self.writer.clone(),
+            mode: self.mode,
+            pending: self.pending.clone(),
+            last_flush: self.last_flush,
+        }
+    }
+}
+
+impl<T, U> fmt::Debug for Source<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source")
+            .field("loader", &self.loader.is_some())
+            .field("writer", &self.writer.is_some())
+            .field("mode", &self.mode)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriteMode, Writer};
+    use crate::{SimpleCacheError, SimpleCacher};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_loader_and_writer() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let loads = Arc::new(AtomicUsize::new(0));
+        let written = Arc::new(Mutex::new(Vec::new()));
+
+        let mut cache = SimpleCacher::new(Duration::from_millis(30));
+        let counter = Arc::clone(&loads);
+        cache.set_loader(move |key: &u32| -> std::io::Result<String> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match key {
+                0 => Err(std::io::Error::other("unavailable")),
+                _ => Ok(format!("loaded{}", key)),
+            }
+        });
+        let log = Arc::clone(&written);
+        cache.set_writer(move |key: &u32, value: &String| -> std::io::Result<()> {
+            if *key == 0 {
+                return Err(std::io::Error::other("read-only"));
+            }
+            log.lock().unwrap().push((*key, value.clone()));
+            Ok(())
+        });
+
+        // Plain lookups never load
+        assert_eq!(cache.get(&1).unwrap_err(), SimpleCacheError::NotFound);
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+        // Misses are loaded once, then served from the cache
+        assert_eq!(cache.get_or_load(&1).unwrap().value(), "loaded1");
+        assert_eq!(cache.get_mut_or_load(&1).unwrap().value(), "loaded1");
+        assert_eq!(cache.get(&1).unwrap().value(), "loaded1");
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // Expired entries are reloaded
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(cache.get_or_load(&1).unwrap().value(), "loaded1");
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+        let err = cache.get_or_load(&0).unwrap_err();
+        assert!(matches!(err, SimpleCacheError::LoadFailed(_)));
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "unavailable"
+        );
+        assert!(!cache.contains_key(&0));
+
+        // Loaded values aren't written back; inserts are written through
+        assert!(written.lock().unwrap().is_empty());
+        cache.insert(2, "two".to_string());
+        assert_eq!(*written.lock().unwrap(), vec![(2, "two".to_string())]);
+
+        // Failed writes leave the cache unchanged
+        assert!(matches!(
+            cache.try_insert(0, "zero".to_string()),
+            Err(SimpleCacheError::WriteFailed(_))
+        ));
+        // The infallible inserts panic instead of dropping the value
+        let tagged = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cache.insert_tagged(0, "zero".to_string(), &["t"])
+        }));
+        assert!(tagged.is_err());
+        assert!(!cache.contains_key(&0));
+        assert!(cache.tags(&0).is_empty());
+
+        cache.clear_loader();
+        assert!(!cache.has_loader());
+        assert_eq!(
+            cache.get_or_load(&5).unwrap_err(),
+            SimpleCacheError::NotFound
+        );
+    }
+
+    #[test]
+    fn test_write_behind() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Mutex;
+
+        type Batches = Arc<Mutex<Vec<Vec<(u32, u32)>>>>;
+
+        struct BatchWriter {
+            batches: Batches,
+            fail: Arc<AtomicBool>,
+        }
+
+        impl Writer<u32, u32> for BatchWriter {
+            type Error = std::io::Error;
+
+            fn write(&self, key: &u32, value: &u32) -> std::io::Result<()> {
+                self.write_batch(&[(key, value)])
+            }
+
+            fn write_batch(&self, entries: &[(&u32, &u32)]) -> std::io::Result<()> {
+                if self.fail.load(Ordering::SeqCst) {
+                    return Err(std::io::Error::other("store down"));
+                }
+                let batch = entries.iter().map(|(k, v)| (**k, **v)).collect();
+                self.batches.lock().unwrap().push(batch);
+                Ok(())
+            }
+        }
+
+        let batches: Batches = Arc::default();
+        let fail = Arc::new(AtomicBool::new(false));
+
+        let mut cache = SimpleCacher::with_max_size(Duration::from_millis(40), 3);
+        cache.set_writer(BatchWriter {
+            batches: Arc::clone(&batches),
+            fail: Arc::clone(&fail),
+        });
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: None,
+        });
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(3, 30);
+        *cache.get_mut(&1).unwrap().value_mut() += 1;
+        assert!(batches.lock().unwrap().is_empty());
+        assert_eq!(cache.dirty_len(), 3);
+
+        // Evicting a dirty entry writes its latest value instead of dropping it
+        cache.insert(4, 40);
+        assert_eq!(*batches.lock().unwrap(), vec![vec![(1, 11)]]);
+
+        // Failed flushes keep everything dirty
+        fail.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            cache.flush(),
+            Err(SimpleCacheError::WriteFailed(_))
+        ));
+        assert_eq!(cache.dirty_len(), 3);
+        fail.store(false, Ordering::SeqCst);
+
+        // Removed entries are dropped with their changes
+        cache.remove(&2);
+        assert_eq!(cache.flush().unwrap(), 2);
+        assert_eq!(batches.lock().unwrap()[1], vec![(3, 30), (4, 40)]);
+        assert_eq!(cache.flush().unwrap(), 0);
+
+        // Expired dirty entries are written by the next flush
+        cache.insert(5, 50);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.cleanup_expired(), 3);
+        assert_eq!(cache.flush().unwrap(), 1);
+        assert_eq!(batches.lock().unwrap()[2], vec![(5, 50)]);
+
+        // Timed flushes run on insert
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: Some(Duration::from_millis(20)),
+        });
+        cache.insert(6, 60);
+        assert_eq!(batches.lock().unwrap().len(), 3);
+        thread::sleep(Duration::from_millis(30));
+        cache.insert(7, 70);
+        assert_eq!(batches.lock().unwrap()[3], vec![(6, 60), (7, 70)]);
+        assert_eq!(cache.dirty_len(), 0);
+
+        // A key evicted again while the writer fails is queued only once,
+        // with its latest value
+        cache.set_write_mode(WriteMode::Behind {
+            flush_interval: None,
+        });
+        fail.store(true, Ordering::SeqCst);
+        let inserts = [
+            (8, 80),
+            (9, 90),
+            (10, 100),
+            (11, 110),
+            (8, 81),
+            (12, 120),
+            (13, 130),
+            (14, 140),
+        ];
+        for (key, value) in inserts {
+            cache.insert(key, value);
+        }
+        assert_eq!(cache.dirty_len(), 7);
+        fail.store(false, Ordering::SeqCst);
+        assert_eq!(cache.flush().unwrap(), 7);
+        assert_eq!(
+            batches.lock().unwrap()[4],
+            vec![
+                (9, 90),
+                (10, 100),
+                (11, 110),
+                (8, 81),
+                (12, 120),
+                (13, 130),
+                (14, 140)
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Saving caches to disk and restoring them on startup.
+//!
+//! Snapshots start with a short header (magic bytes, a format version and the
+//! sequence number of the last journal record they include) followed by the
+//! cache state encoded with bincode through the serde representation in
+//! `snapshot`.
+
+use crate::snapshot::{CacherRef, CacherRepr, CacherReprV1};
+use crate::SimpleCacher;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes identifying a snapshot file.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"SCSN";
+
+/// Current snapshot format version.
+const SNAPSHOT_VERSION: u32 = 3;
+
+/// Snapshot format version without a journal sequence number in the header.
+const SNAPSHOT_VERSION_2: u32 = 2;
+
+/// Snapshot format version that stored "never expires" as `Duration::MAX`.
+const SNAPSHOT_VERSION_1: u32 = 1;
+
+/// Converts a bincode error into an I/O error.
+pub(crate) fn bincode_error(err: bincode::ErrorKind) -> io::Error {
+    match err {
+        bincode::ErrorKind::Io(err) => err,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
+
+impl<T, U> SimpleCacher<T, U>
+where
+    T: Eq + Hash + Serialize,
+    U: Serialize,
+{
+    /// Saves all non-expired entries to a snapshot file.
+    ///
+    /// The snapshot is written to a temporary file next to `path` and then
+    /// renamed over it, so an interrupted save never leaves a truncated
+    /// snapshot behind. Entries store their wall-clock creation time, so their
+    /// remaining TTL keeps counting down while the cache is on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Where to write the snapshot
+    ///
+    /// # Returns
+    ///
+    /// The number of entries written
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use simple_cacher::*;
+    /// use std::time::Duration;
+    ///
+    /// let path = std::env::temp_dir().join("simple_cacher_save_doc.snap");
+    ///
+    /// let mut cache = SimpleCacher::new(Duration::from_secs(300));
+    /// cache.insert("user:1".to_string(), "Alice".to_string());
+    /// assert_eq!(cache.save_to(&path).unwrap(), 1);
+    ///
+    /// let mut restored: SimpleCacher<String, String> = SimpleCacher::load_from(&path).unwrap();
+    /// assert_eq!(restored.get(&"user:1".to_string()).unwrap().value(), "Alice");
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_to<P>(&self, path: P) -> io::Result<usize>
+    where
+        P: AsRef<Path>,
+    {
+        self.save_snapshot(path.as_ref(), 0)
+    }
+
+    /// Saves a snapshot that includes the journal up to record `journal_seq`.
+    pub(crate) fn save_snapshot(&self, path: &Path, journal_seq: u64) -> io::Result<usize> {
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = Path::new(&tmp_name);
+
+        let mut writer = BufWriter::new(File::create(tmp_path)?);
+        let written = self.write_snapshot(&mut writer, journal_seq)?;
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        fs::rename(tmp_path, path)?;
+        Ok(written)
+    }
+
+    /// Writes a snapshot of all non-expired entries to `writer`.
+    pub(crate) fn write_snapshot<W>(&self, mut writer: W, journal_seq: u64) -> io::Result<usize>
+    where
+        W: Write,
+    {
+        let repr = CacherRef::new(self, true);
+
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&journal_seq.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &repr).map_err(|err| bincode_error(*err))?;
+        writer.flush()?;
+
+        Ok(repr.len())
+    }
+}
+
+impl<T, U> SimpleCacher<T, U>
+where
+    T: Clone + Eq + Hash + DeserializeOwned,
+    U: DeserializeOwned,
+{
+    /// Restores a cache from a snapshot written by `save_to`.
+    ///
+    /// The cache keeps the TTL and size settings it was saved with. Each
+    /// entry's age is rebased onto the current clock, so time spent on disk
+    /// counts towards expiry, and entries that expired in the meantime are
+    /// dropped. The key index is not part of the snapshot and has to be
+    /// enabled again if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The snapshot file to read
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, isn't a snapshot, was
+    /// written by an unsupported format version, or doesn't match the key
+    /// and value types.
+    pub fn load_from<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let reader = BufReader::new(File::open(path)?);
+        Self::read_snapshot(reader).map(|(cacher, _)| cacher)
+    }
+
+    /// Reads a snapshot written by `write_snapshot` and drops expired entries.
+    ///
+    /// Also returns the journal sequence number stored in the header, which
+    /// is zero for snapshots written before it was added.
+    pub(crate) fn read_snapshot<R>(mut reader: R) -> io::Result<(Self, u64)>
+    where
+        R: Read,
+    {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a cache snapshot",
+            ));
+        }
+
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        let mut journal_seq = [0u8; 8];
+        if version == SNAPSHOT_VERSION {
+            reader.read_exact(&mut journal_seq)?;
+        }
+        let repr: CacherRepr<T, U> = match version {
+            SNAPSHOT_VERSION | SNAPSHOT_VERSION_2 => bincode::deserialize_from(&mut reader),
+            SNAPSHOT_VERSION_1 => {
+                bincode::deserialize_from::<_, CacherReprV1<T, U>>(&mut reader).map(Into::into)
+            }
+            version => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported snapshot version {}", version),
+                ))
+            }
+        }
+        .map_err(|err| bincode_error(*err))?;
+        let mut cacher = repr.into_cacher();
+        cacher.cleanup_expired();
+        Ok((cacher, u64::from_le_bytes(journal_seq)))
+    }
+}
@@ -0,0 +1,281 @@
+//! Serde support for `SimpleCacher` and `SimpleCacheObject`.
+//!
+//! Entry timestamps are `Instant`s, which only have meaning inside the running
+//! process. Serialized entries therefore store the wall-clock time they were
+//! created at, and deserialization rebases them onto the local monotonic clock
+//! so that the time spent outside the cache still counts towards expiry.
+//! Entries and caches that never expire are stored without a `max_age`.
+//! Version 1 snapshots stored them with a `max_age` of `Duration::MAX`
+//! instead; they are decoded through the `V1` representations.
+
+use crate::{EntryMeta, EntrySource, SimpleCacheObject, SimpleCacher};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Serialize)]
+#[serde(rename = "SimpleCacheObject")]
+struct ObjectRef<'a, U> {
+    value: &'a U,
+    max_age: Option<Duration>,
+    created_at: SystemTime,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "SimpleCacheObject")]
+struct ObjectRepr<U> {
+    value: U,
+    max_age: Option<Duration>,
+    created_at: SystemTime,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "SimpleCacher")]
+pub(crate) struct CacherRef<'a, T, U> {
+    max_age: Option<Duration>,
+    max_size: Option<usize>,
+    entries: Vec<EntryRef<'a, T, U>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Entry")]
+struct EntryRef<'a, T, U> {
+    key: &'a T,
+    entry: &'a SimpleCacheObject<U>,
+    tags: &'a [String],
+    dependencies: &'a [T],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "SimpleCacher")]
+pub(crate) struct CacherRepr<T, U> {
+    max_age: Option<Duration>,
+    max_size: Option<usize>,
+    entries: Vec<EntryRepr<T, U>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Entry")]
+struct EntryRepr<T, U> {
+    key: T,
+    entry: SimpleCacheObject<U>,
+    #[serde(default = "Vec::new")]
+    tags: Vec<String>,
+    #[serde(default = "Vec::new")]
+    dependencies: Vec<T>,
+}
+
+/// Version 1 layout of `ObjectRepr`.
+#[derive(Deserialize)]
+#[serde(rename = "SimpleCacheObject")]
+struct ObjectReprV1<U> {
+    value: U,
+    max_age: Duration,
+    created_at: SystemTime,
+}
+
+/// Version 1 layout of `CacherRepr`.
+#[derive(Deserialize)]
+#[serde(rename = "SimpleCacher")]
+pub(crate) struct CacherReprV1<T, U> {
+    max_age: Duration,
+    max_size: Option<usize>,
+    entries: Vec<EntryReprV1<T, U>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Entry")]
+struct EntryReprV1<T, U> {
+    key: T,
+    entry: ObjectReprV1<U>,
+    #[serde(default = "Vec::new")]
+    tags: Vec<String>,
+    #[serde(default = "Vec::new")]
+    dependencies: Vec<T>,
+}
+
+/// Version 1 stored a missing `max_age` as `Duration::MAX`.
+fn max_age_from_v1(max_age: Duration) -> Option<Duration> {
+    Some(max_age).filter(|&max_age| max_age != Duration::MAX)
+}
+
+impl<T, U> From<CacherReprV1<T, U>> for CacherRepr<T, U> {
+    fn from(repr: CacherReprV1<T, U>) -> Self {
+        let entries = repr
+            .entries
+            .into_iter()
+            .map(|entry| EntryRepr {
+                key: entry.key,
+                entry: SimpleCacheObject::from_wall_clock(
+                    entry.entry.value,
+                    max_age_from_v1(entry.entry.max_age),
+                    entry.entry.created_at,
+                ),
+                tags: entry.tags,
+                dependencies: entry.dependencies,
+            })
+            .collect();
+
+        Self {
+            max_age: max_age_from_v1(repr.max_age),
+            max_size: repr.max_size,
+            entries,
+        }
+    }
+}
+
+impl<'a, T, U> CacherRef<'a, T, U>
+where
+    T: Eq + Hash,
+{
+    /// Borrows the serializable state of `cacher`, optionally leaving out
+    /// entries that have already expired.
+    pub(crate) fn new(cacher: &'a SimpleCacher<T, U>, skip_expired: bool) -> Self {
+        let entries = cacher
+            .cache
+            .iter()
+            .filter(|(_, entry)| !(skip_expired && entry.is_expired()))
+            .map(|(key, entry)| EntryRef {
+                key,
+                entry,
+                tags: cacher.tags.tags(key),
+                dependencies: cacher.deps.parents(key),
+            })
+            .collect();
+
+        Self {
+            max_age: cacher.max_age,
+            max_size: cacher.max_size,
+            entries,
+        }
+    }
+
+    /// Returns the number of entries that will be serialized.
+    #[cfg(feature = "persistence")]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl<T, U> CacherRepr<T, U>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Rebuilds a cache from its deserialized state.
+    pub(crate) fn into_cacher(self) -> SimpleCacher<T, U> {
+        let mut cacher = SimpleCacher::with_limits(self.max_age, self.max_size);
+
+        for entry in self.entries {
+            let tags: Vec<&str> = entry.tags.iter().map(String::as_str).collect();
+            cacher.tags.set(&entry.key, &tags);
+            for parent in &entry.dependencies {
+                cacher.deps.add(&entry.key, parent);
+            }
+            cacher.cache.insert(entry.key, entry.entry);
+        }
+
+        cacher
+    }
+}
+
+impl<U> SimpleCacheObject<U> {
+    /// Returns the wall-clock time this entry was created at.
+    pub(crate) fn created_at_wall_clock(&self) -> SystemTime {
+        let now = SystemTime::now();
+        now.checked_sub(self.created_at.elapsed()).unwrap_or(now)
+    }
+
+    /// Rebuilds an entry created at the given wall-clock time.
+    ///
+    /// If the entry is older than the local monotonic clock can represent,
+    /// it is recreated now with whatever lifetime it has left. Access
+    /// statistics and metadata aren't serialized, so they start over.
+    pub(crate) fn from_wall_clock(
+        value: U,
+        max_age: Option<Duration>,
+        created_at: SystemTime,
+    ) -> Self {
+        let age = SystemTime::now()
+            .duration_since(created_at)
+            .unwrap_or(Duration::ZERO);
+        let now = Instant::now();
+        let meta = EntryMeta::new(EntrySource::Restored, now);
+
+        match now.checked_sub(age) {
+            Some(created_at) => Self {
+                created_at,
+                value,
+                max_age,
+                dirty: false,
+                meta,
+            },
+            None => Self {
+                created_at: now,
+                value,
+                max_age: max_age.map(|max_age| max_age.saturating_sub(age)),
+                dirty: false,
+                meta,
+            },
+        }
+    }
+}
+
+impl<U> Serialize for SimpleCacheObject<U>
+where
+    U: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ObjectRef {
+            value: &self.value,
+            max_age: self.max_age,
+            created_at: self.created_at_wall_clock(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, U> Deserialize<'de> for SimpleCacheObject<U>
+where
+    U: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let repr = ObjectRepr::deserialize(deserializer)?;
+        Ok(Self::from_wall_clock(
+            repr.value,
+            repr.max_age,
+            repr.created_at,
+        ))
+    }
+}
+
+impl<T, U> Serialize for SimpleCacher<T, U>
+where
+    T: Serialize + Eq + Hash,
+    U: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        CacherRef::new(self, false).serialize(serializer)
+    }
+}
+
+impl<'de, T, U> Deserialize<'de> for SimpleCacher<T, U>
+where
+    T: Deserialize<'de> + Clone + Eq + Hash,
+    U: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        CacherRepr::deserialize(deserializer).map(CacherRepr::into_cacher)
+    }
+}
@@ -0,0 +1,502 @@
+//! Insertion-ordered map with constant-time removal.
+//!
+//! `IndexMap` keeps its entries in a dense vector, so removing an entry while
+//! preserving the order of the others shifts every later entry down. `Store`
+//! keeps its entries in a slab of slots instead, linked together in insertion
+//! order, with a hash table mapping keys to slots. Inserting, removing and
+//! popping the oldest entry all take O(1) time, and the slot of an entry stays
+//! the same until the entry is removed.
+
+use hashbrown::HashTable;
+use indexmap::Equivalent;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// An entry together with its links in the insertion order.
+#[derive(Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// Insertion sequence number, used to sort slots without walking the list.
+    seq: u64,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+#[derive(Clone)]
+enum Slot<K, V> {
+    Occupied(Node<K, V>),
+    /// A free slot, linking to the next free one.
+    Vacant(Option<usize>),
+}
+
+/// Returns the node in `slot`, which must be occupied.
+fn occupied<K, V>(slots: &[Slot<K, V>], slot: usize) -> &Node<K, V> {
+    match &slots[slot] {
+        Slot::Occupied(node) => node,
+        Slot::Vacant(_) => unreachable!("slot {} is vacant", slot),
+    }
+}
+
+fn occupied_mut<K, V>(slots: &mut [Slot<K, V>], slot: usize) -> &mut Node<K, V> {
+    match &mut slots[slot] {
+        Slot::Occupied(node) => node,
+        Slot::Vacant(_) => unreachable!("slot {} is vacant", slot),
+    }
+}
+
+fn make_hash<Q>(hasher: &RandomState, key: &Q) -> u64
+where
+    Q: ?Sized + Hash,
+{
+    let mut state = hasher.build_hasher();
+    key.hash(&mut state);
+    state.finish()
+}
+
+/// Map from keys to values that remembers insertion order.
+///
+/// Replacing the value of an existing key keeps its place in the order.
+#[derive(Clone)]
+pub(crate) struct Store<K, V> {
+    slots: Vec<Slot<K, V>>,
+    /// Slots of all entries, hashed by their keys.
+    table: HashTable<usize>,
+    hasher: RandomState,
+    /// Oldest entry.
+    head: Option<usize>,
+    /// Newest entry.
+    tail: Option<usize>,
+    /// First free slot.
+    free: Option<usize>,
+    len: usize,
+    next_seq: u64,
+}
+
+impl<K, V> Default for Store<K, V> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            table: HashTable::new(),
+            hasher: RandomState::new(),
+            head: None,
+            tail: None,
+            free: None,
+            len: 0,
+            next_seq: 0,
+        }
+    }
+}
+
+impl<K, V> Store<K, V> {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the entry in `slot`, or `None` if the slot is free.
+    pub(crate) fn get_slot(&self, slot: usize) -> Option<(&K, &V)> {
+        match self.slots.get(slot)? {
+            Slot::Occupied(node) => Some((&node.key, &node.value)),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Sorts occupied slots from the oldest to the newest entry.
+    pub(crate) fn sort_by_order(&self, slots: &mut [usize]) {
+        slots.sort_unstable_by_key(|&slot| occupied(&self.slots, slot).seq);
+    }
+
+    /// Iterates over the entries from the oldest to the newest, with their slots.
+    pub(crate) fn iter_full(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: &self.slots,
+            next: self.head,
+            remaining: self.len,
+        }
+    }
+
+    /// Iterates over the entries from the oldest to the newest.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.iter_full().map(|(_, key, value)| (key, value))
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Iterates over the values in no particular order.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Occupied(node) => Some(&node.value),
+            Slot::Vacant(_) => None,
+        })
+    }
+
+    /// Iterates mutably over the values in no particular order.
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut V> + '_ {
+        self.slots.iter_mut().filter_map(|slot| match slot {
+            Slot::Occupied(node) => Some(&mut node.value),
+            Slot::Vacant(_) => None,
+        })
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.table.clear();
+        self.head = None;
+        self.tail = None;
+        self.free = None;
+        self.len = 0;
+    }
+
+    /// Takes the node out of an occupied slot that has already been removed
+    /// from the hash table, and frees the slot.
+    fn unlink(&mut self, slot: usize) -> (K, V) {
+        let Slot::Occupied(node) =
+            std::mem::replace(&mut self.slots[slot], Slot::Vacant(self.free))
+        else {
+            unreachable!("slot {} is vacant", slot);
+        };
+        self.free = Some(slot);
+        self.len -= 1;
+
+        match node.prev {
+            Some(prev) => occupied_mut(&mut self.slots, prev).next = node.next,
+            None => self.head = node.next,
+        }
+        match node.next {
+            Some(next) => occupied_mut(&mut self.slots, next).prev = node.prev,
+            None => self.tail = node.prev,
+        }
+        (node.key, node.value)
+    }
+}
+
+impl<K, V> Store<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Returns the slot holding `key`.
+    pub(crate) fn slot_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash(&self.hasher, key);
+        self.table
+            .find(hash, |&slot| {
+                key.equivalent(&occupied(&self.slots, slot).key)
+            })
+            .copied()
+    }
+
+    pub(crate) fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_full(key).map(|(_, _, value)| value)
+    }
+
+    /// Returns the slot, key and value of `key`.
+    pub(crate) fn get_full<Q>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let slot = self.slot_of(key)?;
+        let node = occupied(&self.slots, slot);
+        Some((slot, &node.key, &node.value))
+    }
+
+    pub(crate) fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let slot = self.slot_of(key)?;
+        Some(&mut occupied_mut(&mut self.slots, slot).value)
+    }
+
+    pub(crate) fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.slot_of(key).is_some()
+    }
+
+    /// Inserts an entry as the newest one, or replaces the value of an
+    /// existing key in place and returns the old value.
+    pub(crate) fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(slot) = self.slot_of(&key) {
+            let node = occupied_mut(&mut self.slots, slot);
+            return Some(std::mem::replace(&mut node.value, value));
+        }
+
+        let node = Node {
+            key,
+            value,
+            seq: self.next_seq,
+            prev: self.tail,
+            next: None,
+        };
+        self.next_seq += 1;
+
+        let slot = match self.free {
+            Some(slot) => {
+                let Slot::Vacant(next_free) = self.slots[slot] else {
+                    unreachable!("free slot {} is occupied", slot);
+                };
+                self.free = next_free;
+                self.slots[slot] = Slot::Occupied(node);
+                slot
+            }
+            None => {
+                self.slots.push(Slot::Occupied(node));
+                self.slots.len() - 1
+            }
+        };
+
+        match self.tail {
+            Some(tail) => occupied_mut(&mut self.slots, tail).next = Some(slot),
+            None => self.head = Some(slot),
+        }
+        self.tail = Some(slot);
+        self.len += 1;
+
+        let (slots, hasher) = (&self.slots, &self.hasher);
+        let hash = make_hash(hasher, &occupied(slots, slot).key);
+        self.table.insert_unique(hash, slot, |&other| {
+            make_hash(hasher, &occupied(slots, other).key)
+        });
+        None
+    }
+
+    /// Removes `key` and returns its entry.
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash(&self.hasher, key);
+        let slots = &self.slots;
+        let (slot, _) = self
+            .table
+            .find_entry(hash, |&slot| key.equivalent(&occupied(slots, slot).key))
+            .ok()?
+            .remove();
+        Some(self.unlink(slot))
+    }
+
+    /// Removes the entry in `slot`, or returns `None` if the slot is free.
+    pub(crate) fn remove_slot(&mut self, slot: usize) -> Option<(K, V)> {
+        self.get_slot(slot)?;
+        let hash = make_hash(&self.hasher, &occupied(&self.slots, slot).key);
+        self.table
+            .find_entry(hash, |&other| other == slot)
+            .expect("occupied slots are in the table")
+            .remove();
+        Some(self.unlink(slot))
+    }
+
+    /// Removes and returns the oldest entry.
+    pub(crate) fn pop_front(&mut self) -> Option<(K, V)> {
+        self.remove_slot(self.head?)
+    }
+
+    /// Removes every entry for which `pred` returns `true`, in a single pass,
+    /// and returns them from the oldest to the newest.
+    pub(crate) fn extract_if<F>(&mut self, mut pred: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut extracted = Vec::new();
+        let mut cursor = self.head;
+        while let Some(slot) = cursor {
+            let node = occupied_mut(&mut self.slots, slot);
+            cursor = node.next;
+            if pred(&node.key, &mut node.value) {
+                extracted.extend(self.remove_slot(slot));
+            }
+        }
+        extracted
+    }
+}
+
+impl<K, V> fmt::Debug for Store<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the entries of a `Store` in insertion order.
+pub(crate) struct Iter<'a, K, V> {
+    slots: &'a [Slot<K, V>],
+    next: Option<usize>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (usize, &'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.next?;
+        let node = occupied(self.slots, slot);
+        self.next = node.next;
+        self.remaining -= 1;
+        Some((slot, &node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_order() {
+        let mut store = Store::new();
+        for i in 0..6 {
+            assert_eq!(store.insert(i, i * 10), None);
+        }
+
+        // Removing from the middle keeps the order of the rest
+        assert_eq!(store.remove(&2), Some((2, 20)));
+        assert_eq!(store.remove(&2), None);
+        let slot = store.slot_of(&4).unwrap();
+        assert_eq!(store.remove_slot(slot), Some((4, 40)));
+        assert_eq!(store.remove_slot(slot), None);
+        assert_eq!(store.pop_front(), Some((0, 0)));
+
+        // Freed slots are reused, but new entries still go last
+        assert_eq!(store.insert(6, 60), None);
+        assert_eq!(store.insert(2, 21), None);
+        assert_eq!(store.insert(3, 31), Some(30));
+        let keys: Vec<i32> = store.keys().copied().collect();
+        assert_eq!(keys, [1, 3, 5, 6, 2]);
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.iter_full().len(), 5);
+
+        let mut slots: Vec<usize> = [2, 6, 1].iter().filter_map(|k| store.slot_of(k)).collect();
+        store.sort_by_order(&mut slots);
+        let sorted: Vec<i32> = slots
+            .iter()
+            .map(|&s| *store.get_slot(s).unwrap().0)
+            .collect();
+        assert_eq!(sorted, [1, 6, 2]);
+
+        let copy = store.clone();
+        let odd = store.extract_if(|key, _| key % 2 == 1);
+        assert_eq!(odd, [(1, 10), (3, 31), (5, 50)]);
+        let keys: Vec<i32> = store.keys().copied().collect();
+        assert_eq!(keys, [6, 2]);
+        assert_eq!(copy.len(), 5);
+
+        store.clear();
+        assert!(store.is_empty());
+        assert_eq!(store.pop_front(), None);
+        store.insert(7, 70);
+        assert_eq!(store.get(&7), Some(&70));
+    }
+
+    /// Returns the keys from the oldest to the newest, walking the links in
+    /// both directions.
+    fn keys_both_ways(store: &Store<i32, i32>) -> Vec<i32> {
+        let forward: Vec<i32> = store.keys().copied().collect();
+        let mut backward = Vec::new();
+        let mut cursor = store.tail;
+        while let Some(slot) = cursor {
+            let node = occupied(&store.slots, slot);
+            backward.push(node.key);
+            cursor = node.prev;
+        }
+        backward.reverse();
+        assert_eq!(forward, backward);
+        forward
+    }
+
+    #[test]
+    fn test_remove_head_and_tail() {
+        let mut store = Store::new();
+        for i in 0..4 {
+            store.insert(i, i * 10);
+        }
+
+        assert_eq!(store.remove(&0), Some((0, 0)));
+        assert_eq!(keys_both_ways(&store), [1, 2, 3]);
+        assert_eq!(store.remove(&3), Some((3, 30)));
+        assert_eq!(keys_both_ways(&store), [1, 2]);
+
+        // New entries are linked after the new tail
+        store.insert(4, 40);
+        assert_eq!(keys_both_ways(&store), [1, 2, 4]);
+
+        assert_eq!(store.remove(&1), Some((1, 10)));
+        assert_eq!(store.remove(&4), Some((4, 40)));
+        assert_eq!(keys_both_ways(&store), [2]);
+        assert_eq!(store.remove(&2), Some((2, 20)));
+        assert!(store.is_empty());
+        assert_eq!((store.head, store.tail), (None, None));
+        assert_eq!(store.pop_front(), None);
+    }
+
+    #[test]
+    fn test_reuse_after_clear() {
+        let mut store = Store::new();
+        for i in 0..3 {
+            store.insert(i, i);
+        }
+        store.remove(&1);
+        store.clear();
+        assert_eq!(store.free, None);
+
+        store.insert(5, 50);
+        store.insert(6, 60);
+        assert_eq!(store.slot_of(&5), Some(0));
+        assert_eq!(store.get(&0), None);
+        assert_eq!(keys_both_ways(&store), [5, 6]);
+        assert_eq!(store.iter_full().len(), 2);
+
+        // The slot freed before the clear is not handed out twice
+        store.remove(&5);
+        store.insert(7, 70);
+        store.insert(8, 80);
+        assert_eq!(keys_both_ways(&store), [6, 7, 8]);
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_extract_consecutive() {
+        let mut store = Store::new();
+        for i in 0..8 {
+            store.insert(i, i);
+        }
+
+        // Runs of removed nodes at the head, in the middle and at the tail
+        let removed = store.extract_if(|key, _| matches!(key, 0 | 1 | 3 | 4 | 5 | 7));
+        let keys: Vec<i32> = removed.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [0, 1, 3, 4, 5, 7]);
+        assert_eq!(keys_both_ways(&store), [2, 6]);
+
+        let removed = store.extract_if(|_, _| true);
+        assert_eq!(removed, [(2, 2), (6, 6)]);
+        assert!(store.is_empty());
+        assert_eq!((store.head, store.tail), (None, None));
+
+        store.insert(9, 9);
+        assert_eq!(keys_both_ways(&store), [9]);
+    }
+}
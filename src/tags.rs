@@ -0,0 +1,91 @@
+//! Tag-to-key index used for grouping and bulk invalidation.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Bidirectional mapping between cache keys and the tags attached to them.
+///
+/// Both directions are kept so that removing a single key (on eviction,
+/// expiry or explicit removal) doesn't require scanning every tag.
+#[derive(Debug, Clone)]
+pub(crate) struct TagIndex<T> {
+    by_tag: HashMap<String, HashSet<T>>,
+    by_key: HashMap<T, Vec<String>>,
+}
+
+impl<T> Default for TagIndex<T> {
+    fn default() -> Self {
+        Self {
+            by_tag: HashMap::new(),
+            by_key: HashMap::new(),
+        }
+    }
+}
+
+impl<T> TagIndex<T>
+where
+    T: Eq + Hash,
+{
+    /// Replaces the tags attached to `key`.
+    pub(crate) fn set(&mut self, key: &T, tags: &[&str])
+    where
+        T: Clone,
+    {
+        self.remove_key(key);
+        if tags.is_empty() {
+            return;
+        }
+
+        let mut owned: Vec<String> = Vec::with_capacity(tags.len());
+        for tag in tags {
+            if owned.iter().any(|t| t == tag) {
+                continue;
+            }
+            self.by_tag
+                .entry((*tag).to_string())
+                .or_default()
+                .insert(key.clone());
+            owned.push((*tag).to_string());
+        }
+        self.by_key.insert(key.clone(), owned);
+    }
+
+    /// Detaches every tag from `key`.
+    pub(crate) fn remove_key(&mut self, key: &T) {
+        let Some(tags) = self.by_key.remove(key) else {
+            return;
+        };
+        for tag in tags {
+            if let Some(keys) = self.by_tag.get_mut(&tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.by_tag.remove(&tag);
+                }
+            }
+        }
+    }
+
+    /// Returns the tags attached to `key`.
+    pub(crate) fn tags(&self, key: &T) -> &[String] {
+        self.by_key.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns the keys carrying `tag`.
+    pub(crate) fn keys(&self, tag: &str) -> impl Iterator<Item = &T> {
+        self.by_tag.get(tag).into_iter().flatten()
+    }
+
+    /// Removes `tag` from the index and returns the keys that carried it.
+    ///
+    /// The returned keys still have their other tags attached; callers remove
+    /// them with `remove_key` as the entries are dropped.
+    pub(crate) fn take(&mut self, tag: &str) -> HashSet<T> {
+        self.by_tag.remove(tag).unwrap_or_default()
+    }
+
+    /// Removes all tags.
+    pub(crate) fn clear(&mut self) {
+        self.by_tag.clear();
+        self.by_key.clear();
+    }
+}
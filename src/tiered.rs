@@ -0,0 +1,488 @@
+//! Two-tier cache that spills evicted entries to disk.
+
+use crate::persist::bincode_error;
+use crate::store::Store;
+use crate::{EntryMeta, SimpleCacheError, SimpleCacheObject, SimpleCacher};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// File name prefix of spilled entries, so that other files in the
+/// directory are left alone.
+const ENTRY_PREFIX: &str = "simple-cacher-spill-";
+
+/// File extension used for spilled entries.
+const ENTRY_EXTENSION: &str = "entry";
+
+/// Location, lifetime and metadata of an entry stored on disk.
+#[derive(Debug, Clone)]
+struct DiskEntry {
+    id: u64,
+    created_at: Instant,
+    max_age: Option<Duration>,
+    meta: EntryMeta,
+}
+
+impl DiskEntry {
+    fn is_expired(&self) -> bool {
+        self.max_age
+            .is_some_and(|max_age| self.created_at.elapsed() > max_age)
+    }
+
+    fn expired_for(&self) -> Duration {
+        self.max_age.map_or(Duration::ZERO, |max_age| {
+            self.created_at.elapsed().saturating_sub(max_age)
+        })
+    }
+}
+
+/// A cache with an in-memory tier backed by an on-disk overflow tier.
+///
+/// New entries always go to memory. When the memory tier is full, its oldest
+/// entries are written to the disk tier instead of being dropped, one file per
+/// entry. A `get` for an entry on disk moves it back into memory, which may in
+/// turn spill another entry. Each tier has its own size limit and evicts its
+/// oldest entries first; entries evicted from the disk tier are dropped.
+///
+/// Entries keep their original creation time and TTL while on disk. The disk
+/// tier is a scratch area rather than persistence: spill files left in the
+/// directory by a previous run are deleted when the cache is opened. Spill
+/// files are named `simple-cacher-spill-<n>.entry`; other files in the
+/// directory are never touched, but two caches must not share a directory.
+/// Tags and dependencies are not carried over to the disk tier.
+///
+/// # Examples
+///
+/// ```rust
+/// use simple_cacher::*;
+/// use std::time::Duration;
+///
+/// let dir = std::env::temp_dir().join("simple_cacher_tiered_doc");
+/// let mut cache = TieredCacher::open(&dir, Duration::from_secs(300), 2, 100).unwrap();
+///
+/// cache.insert("a".to_string(), "first".to_string()).unwrap();
+/// cache.insert("b".to_string(), "second".to_string()).unwrap();
+/// cache.insert("c".to_string(), "third".to_string()).unwrap(); // "a" spills to disk
+///
+/// assert_eq!(cache.memory_len(), 2);
+/// assert_eq!(cache.disk_len(), 1);
+///
+/// // Promoted back into memory, spilling "b"
+/// assert_eq!(cache.get(&"a".to_string()).unwrap().value(), "first");
+/// assert!(cache.memory().contains_key(&"a".to_string()));
+/// # cache.clear().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TieredCacher<T, U> {
+    memory: SimpleCacher<T, U>,
+    disk: Store<T, DiskEntry>,
+    dir: PathBuf,
+    max_disk_size: usize,
+    next_id: u64,
+}
+
+impl<T, U> TieredCacher<T, U>
+where
+    T: Clone + Eq + Hash,
+    U: Serialize + DeserializeOwned,
+{
+    /// Opens a tiered cache that spills to files in `dir`.
+    ///
+    /// The directory is created if needed, and spill files from a previous
+    /// run are removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `max_memory_size` is zero, since
+    /// every entry would then go straight to disk. Otherwise returns an error
+    /// if the directory can't be created or cleaned up.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory for the disk tier
+    /// * `max_age` - Default time-to-live for cache entries
+    /// * `max_memory_size` - Maximum number of entries kept in memory
+    /// * `max_disk_size` - Maximum number of entries kept on disk
+    pub fn open<P>(
+        dir: P,
+        max_age: Duration,
+        max_memory_size: usize,
+        max_disk_size: usize,
+    ) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        if max_memory_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "memory tier must hold at least one entry",
+            ));
+        }
+
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if is_spill_file(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(Self {
+            memory: SimpleCacher::with_max_size(max_age, max_memory_size),
+            disk: Store::new(),
+            dir,
+            max_disk_size,
+            next_id: 0,
+        })
+    }
+
+    /// Inserts an entry with the default TTL into the memory tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry spilled to make room can't be written to
+    /// disk. The new entry is inserted regardless; the spilled one is lost.
+    pub fn insert(&mut self, key: T, value: U) -> io::Result<()> {
+        let cache_obj = SimpleCacheObject::new(value, self.memory.max_age);
+        self.insert_object(key, cache_obj)
+    }
+
+    /// Inserts an entry with a custom TTL into the memory tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry spilled to make room can't be written to
+    /// disk. The new entry is inserted regardless; the spilled one is lost.
+    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) -> io::Result<()> {
+        let cache_obj = SimpleCacheObject::new(value, Some(ttl));
+        self.insert_object(key, cache_obj)
+    }
+
+    /// Retrieves an entry from either tier.
+    ///
+    /// Entries found on disk are promoted into memory. If a spill file can't
+    /// be read, the entry is dropped and reported as not found, or as
+    /// `SimpleCacheError::SerializationFailed` if its contents are corrupt.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(&SimpleCacheObject<U>)` - The cached entry if found and not expired
+    /// * `Err(SimpleCacheError::NotFound)` - The key doesn't exist in either tier
+    /// * `Err(SimpleCacheError::Expired { .. })` - The entry existed but expired (now removed)
+    pub fn get(&mut self, key: &T) -> Result<&SimpleCacheObject<U>, SimpleCacheError> {
+        if !self.memory.cache.contains_key(key) {
+            self.promote(key)?;
+        }
+        self.memory.get(key)
+    }
+
+    /// Removes an entry from whichever tier holds it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry is on disk and its file can't be read.
+    pub fn remove(&mut self, key: &T) -> io::Result<Option<SimpleCacheObject<U>>> {
+        if let Some(removed) = self.memory.remove(key) {
+            return Ok(Some(removed));
+        }
+
+        match self.disk.remove(key) {
+            Some((_, entry)) => self.take_file(&entry).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks if a key exists in either tier and is not expired.
+    pub fn contains_key(&self, key: &T) -> bool {
+        self.memory.contains_key(key) || self.disk.get(key).is_some_and(|e| !e.is_expired())
+    }
+
+    /// Removes expired entries from both tiers.
+    ///
+    /// # Returns
+    ///
+    /// The number of expired entries that were removed
+    pub fn cleanup_expired(&mut self) -> usize {
+        let mut removed = self.memory.cleanup_expired();
+
+        for (_, entry) in self.disk.extract_if(|_, entry| entry.is_expired()) {
+            let _ = fs::remove_file(self.entry_path(entry.id));
+            removed += 1;
+        }
+
+        removed
+    }
+
+    /// Removes all entries from both tiers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a spill file can't be deleted.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.memory.clear();
+        let ids: Vec<u64> = self.disk.values().map(|entry| entry.id).collect();
+        self.disk.clear();
+        for id in ids {
+            match fs::remove_file(self.entry_path(id)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the total number of entries in both tiers (including expired ones).
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.disk.len()
+    }
+
+    /// Returns `true` if both tiers are empty.
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.disk.is_empty()
+    }
+
+    /// Returns the number of entries in the memory tier.
+    pub fn memory_len(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Returns the number of entries in the disk tier.
+    pub fn disk_len(&self) -> usize {
+        self.disk.len()
+    }
+
+    /// Returns the memory tier for read-only queries.
+    pub fn memory(&self) -> &SimpleCacher<T, U> {
+        &self.memory
+    }
+
+    fn insert_object(&mut self, key: T, cache_obj: SimpleCacheObject<U>) -> io::Result<()> {
+        if let Some((_, stale)) = self.disk.remove(&key) {
+            let _ = fs::remove_file(self.entry_path(stale.id));
+        }
+
+        let (_, evicted) = self.memory.insert_object_evicting(key, cache_obj);
+        self.spill(evicted)
+    }
+
+    /// Moves an entry from disk into memory.
+    fn promote(&mut self, key: &T) -> Result<(), SimpleCacheError> {
+        let (key, entry) = self.disk.remove(key).ok_or(SimpleCacheError::NotFound)?;
+
+        if entry.is_expired() {
+            let _ = fs::remove_file(self.entry_path(entry.id));
+            return Err(SimpleCacheError::Expired {
+                expired_for: entry.expired_for(),
+            });
+        }
+
+        let cache_obj = self.take_file(&entry).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData => SimpleCacheError::SerializationFailed(Arc::new(err)),
+            _ => SimpleCacheError::NotFound,
+        })?;
+        let (_, evicted) = self.memory.insert_object_evicting(key, cache_obj);
+        // Failing to spill only loses the displaced entry, which a cache may do.
+        let _ = self.spill(evicted);
+        Ok(())
+    }
+
+    /// Writes entries evicted from memory to the disk tier.
+    ///
+    /// Every entry is attempted even if an earlier one fails, and the first
+    /// error is returned. Entries that can't be written are dropped.
+    fn spill(&mut self, evicted: Vec<(T, SimpleCacheObject<U>)>) -> io::Result<()> {
+        let mut result = Ok(());
+        for (key, cache_obj) in evicted {
+            if cache_obj.is_expired() || self.max_disk_size == 0 {
+                continue;
+            }
+
+            while self.disk.len() >= self.max_disk_size {
+                let Some((_, oldest)) = self.disk.pop_front() else {
+                    break;
+                };
+                let _ = fs::remove_file(self.entry_path(oldest.id));
+            }
+
+            let id = self.next_id;
+            self.next_id += 1;
+
+            if let Err(err) = self.write_file(id, &cache_obj.value) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+                continue;
+            }
+
+            let entry = DiskEntry {
+                id,
+                created_at: cache_obj.created_at,
+                max_age: cache_obj.max_age,
+                meta: cache_obj.meta,
+            };
+            self.disk.insert(key, entry);
+        }
+        result
+    }
+
+    /// Writes a spilled value to its file, deleting a partly written file
+    /// if that fails.
+    fn write_file(&self, id: u64, value: &U) -> io::Result<()> {
+        let path = self.entry_path(id);
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            bincode::serialize_into(&mut writer, value).map_err(|err| bincode_error(*err))?;
+            writer.flush()
+        };
+
+        write().map_err(|err| {
+            let _ = fs::remove_file(&path);
+            err
+        })
+    }
+
+    /// Reads an entry's file back and deletes it.
+    fn take_file(&self, entry: &DiskEntry) -> io::Result<SimpleCacheObject<U>> {
+        let path = self.entry_path(entry.id);
+        let reader = BufReader::new(File::open(&path)?);
+        let value = bincode::deserialize_from(reader).map_err(|err| bincode_error(*err));
+        let _ = fs::remove_file(&path);
+
+        Ok(SimpleCacheObject {
+            created_at: entry.created_at,
+            value: value?,
+            max_age: entry.max_age,
+            dirty: false,
+            meta: entry.meta.clone(),
+        })
+    }
+
+    fn entry_path(&self, id: u64) -> PathBuf {
+        self.dir
+            .join(format!("{}{}.{}", ENTRY_PREFIX, id, ENTRY_EXTENSION))
+    }
+}
+
+/// Returns `true` if `path` names a file written by `TieredCacher::spill`.
+fn is_spill_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == ENTRY_EXTENSION)
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(ENTRY_PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serializer};
+    use std::thread;
+
+    /// A value whose serialization fails part way through when `fails` is set.
+    #[derive(Debug, Deserialize)]
+    struct Fragile {
+        fails: bool,
+    }
+
+    impl Serialize for Fragile {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            use serde::ser::{Error, SerializeStruct};
+
+            let mut state = serializer.serialize_struct("Fragile", 1)?;
+            state.serialize_field("fails", &self.fails)?;
+            if self.fails {
+                return Err(S::Error::custom("unserializable"));
+            }
+            state.end()
+        }
+    }
+
+    #[test]
+    fn test_spill_failure() {
+        let dir =
+            std::env::temp_dir().join(format!("simple_cacher_tiered_spill_{}", std::process::id()));
+        let mut cache = TieredCacher::open(&dir, Duration::from_secs(60), 1, 10).unwrap();
+
+        let evicted = [(1u32, false), (2, true), (3, false), (4, true)]
+            .into_iter()
+            .map(|(key, fails)| (key, SimpleCacheObject::new(Fragile { fails }, None)))
+            .collect();
+        let err = cache.spill(evicted).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // The entries after the failed one are still spilled, and the failed
+        // ones leave no partial files behind
+        assert!(cache.contains_key(&1) && cache.contains_key(&3));
+        assert_eq!(cache.disk_len(), 2);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        cache.clear().unwrap();
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tiered_spill_and_promote() {
+        let dir = std::env::temp_dir().join(format!("simple_cacher_tiered_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("simple-cacher-spill-99.entry"), b"stale").unwrap();
+        std::fs::write(dir.join("99.entry"), b"not ours").unwrap();
+
+        assert_eq!(
+            TieredCacher::<u32, Vec<u8>>::open(&dir, Duration::from_secs(60), 0, 2)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+        let mut cache = TieredCacher::open(&dir, Duration::from_secs(60), 2, 2).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_file(dir.join("99.entry")).unwrap();
+
+        cache.insert(1u32, vec![1u8]).unwrap();
+        cache.insert(2, vec![2]).unwrap();
+        cache.insert(3, vec![3]).unwrap();
+        cache
+            .insert_with_ttl(4, vec![4], Duration::from_millis(30))
+            .unwrap();
+        assert_eq!((cache.memory_len(), cache.disk_len()), (2, 2));
+
+        // Promoting 1 spills 3
+        assert_eq!(cache.get(&1).unwrap().value(), &vec![1]);
+        assert_eq!((cache.memory_len(), cache.disk_len()), (2, 2));
+        assert!(cache.contains_key(&2) && cache.contains_key(&3));
+
+        // Spilling 4 into the full disk tier drops 2
+        cache.insert(5, vec![5]).unwrap();
+        assert_eq!((cache.memory_len(), cache.disk_len()), (2, 2));
+        assert!(!cache.contains_key(&2));
+
+        // Re-inserting a spilled key replaces the disk copy
+        cache.insert(3, vec![30]).unwrap();
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.get(&3).unwrap().value(), &vec![30]);
+
+        // Entry 4 expires while on disk
+        thread::sleep(Duration::from_millis(50));
+        assert!(matches!(
+            cache.get(&4),
+            Err(SimpleCacheError::Expired { .. })
+        ));
+
+        let removed = cache.remove(&1).unwrap().unwrap();
+        assert_eq!(removed.into_value(), vec![1]);
+        assert!(cache.remove(&1).unwrap().is_none());
+
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}